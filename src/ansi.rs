@@ -0,0 +1,354 @@
+//! ansi module
+
+use crate::printable;
+use crate::tab::TabExpand;
+use std::cmp;
+use unicode_width::UnicodeWidthStr;
+
+/// Range of the text that is highlighted with the SGR sequence.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Highlight {
+    pub start: usize,
+    pub end: usize,
+    pub sgr: String,
+}
+
+impl Highlight {
+    pub fn new(start: usize, end: usize, sgr: &str) -> Self {
+        Self {
+            start,
+            end,
+            sgr: sgr.to_owned(),
+        }
+    }
+}
+
+/// Merge the layers of highlights into the sorted ranges that don't overlap.
+/// The highlights of the former layers are drawn over the latter ones.
+/// Each layer must be sorted and must not overlap itself.
+pub fn merge_highlights(layers: Vec<Vec<Highlight>>) -> Vec<Highlight> {
+    let mut layers = layers.into_iter().filter(|l| !l.is_empty());
+    let mut merged = layers.next().unwrap_or_default();
+    for layer in layers {
+        let mut uncovered = vec![];
+        for hl in layer {
+            let mut start = hl.start;
+            // the ranges of the upper layers that overlap the highlight
+            for upper in merged
+                .iter()
+                .filter(|u| u.start < hl.end && u.end > hl.start)
+            {
+                if start < upper.start {
+                    uncovered.push(Highlight::new(start, upper.start, &hl.sgr));
+                }
+                start = cmp::max(start, upper.end);
+            }
+            if start < hl.end {
+                uncovered.push(Highlight::new(start, hl.end, &hl.sgr));
+            }
+        }
+        merged.extend(uncovered);
+        merged.sort_by_key(|hl| hl.start);
+    }
+    merged
+}
+
+/// Printable text whose SGR (Select Graphic Rendition) sequences are taken out.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SgrText {
+    /// text without escape sequences
+    pub text: String,
+    /// SGR sequences and the byte offsets of the text where they are placed
+    pub sgrs: Vec<(usize, String)>,
+}
+
+impl SgrText {
+    /// Parse the line.
+    /// SGR sequences are kept and the other escape sequences are dropped.
+    pub fn parse(raw: &str) -> Self {
+        let mut text = String::new();
+        let mut sgrs = vec![];
+        let mut chars = raw.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            if c != '\x1b' {
+                text.push(c);
+                continue;
+            }
+            match chars.peek() {
+                Some('[') => {
+                    // CSI: parameter bytes, intermediate bytes and final byte
+                    chars.next();
+                    let mut seq = String::from("\x1b[");
+                    let mut final_byte = None;
+                    while let Some(&c) = chars.peek() {
+                        if ('\x40'..='\x7e').contains(&c) {
+                            chars.next();
+                            seq.push(c);
+                            final_byte = Some(c);
+                            break;
+                        } else if ('\x20'..='\x3f').contains(&c) {
+                            chars.next();
+                            seq.push(c);
+                        } else {
+                            // malformed sequence
+                            break;
+                        }
+                    }
+                    if final_byte == Some('m') {
+                        sgrs.push((text.len(), seq));
+                    }
+                }
+                Some(']') => {
+                    // OSC: terminated by BEL or ST
+                    chars.next();
+                    while let Some(c) = chars.next() {
+                        if c == '\x07' {
+                            break;
+                        } else if c == '\x1b' {
+                            chars.next_if_eq(&'\\');
+                            break;
+                        }
+                    }
+                }
+                Some(_) => {
+                    // two bytes sequence
+                    chars.next();
+                }
+                None => {}
+            }
+        }
+        Self { text, sgrs }
+    }
+
+    /// Replace tabs of the text with spaces, keeping the SGR positions.
+    /// The markers of control chars and invalid bytes are styled.
+    pub fn expand_tab(&self, tab_width: usize) -> Self {
+        let mut expanded = Self::default();
+        let mut column = 0;
+        let mut copied = 0;
+
+        for (pos, sgr) in self.sgrs.iter() {
+            column = expanded.push_expanded(&self.text[copied..*pos], tab_width, column);
+            expanded.sgrs.push((expanded.text.len(), sgr.clone()));
+            copied = *pos;
+        }
+        expanded.push_expanded(&self.text[copied..], tab_width, column);
+        expanded
+    }
+
+    /// Push the text with tabs expanded, and return the column after it.
+    fn push_expanded(&mut self, text: &str, tab_width: usize, column: usize) -> usize {
+        let (text, markers) = text.expand_tab_marked(tab_width, column);
+        let base = self.text.len();
+        for m in markers {
+            self.sgrs
+                .push((base + m.start, printable::MARKER_SGR.to_owned()));
+            self.sgrs
+                .push((base + m.end, printable::MARKER_END_SGR.to_owned()));
+        }
+        self.text.push_str(&text);
+        column + UnicodeWidthStr::width_cjk(text.as_str())
+    }
+
+    /// Decorate the range of the text with the SGR sequences.
+    /// The SGR state before the range is carried over,
+    /// and the highlight ranges are drawn on top of it.
+    pub fn decorate(&self, range: (usize, usize), highlights: &[Highlight]) -> String {
+        let (start, end) = range;
+        let mut decorated = String::new();
+        let mut state = String::new();
+        let mut sgrs = self.sgrs.iter().peekable();
+
+        // SGR state at the start of the range
+        while let Some((_, sgr)) = sgrs.next_if(|(pos, _)| *pos <= start) {
+            apply_sgr(&mut state, sgr);
+        }
+        decorated.push_str(&state);
+
+        let mut active: Option<&Highlight> = None;
+        let mut highlights = highlights.iter().peekable();
+        for (i, c) in self.text[start..end].char_indices() {
+            let pos = start + i;
+            while highlights.next_if(|hl| hl.end <= pos).is_some() {}
+            let hl = highlights.peek().copied().filter(|hl| hl.start <= pos);
+            if hl != active && active.is_some() {
+                decorated.push_str(&format!("{}{}", termion::style::Reset, state));
+            }
+            // the highlight is drawn again over the new SGR state
+            let mut restyles = hl != active;
+            while let Some((_, sgr)) = sgrs.next_if(|(p, _)| *p <= pos) {
+                apply_sgr(&mut state, sgr);
+                decorated.push_str(sgr);
+                restyles = true;
+            }
+            if let Some(hl) = hl.filter(|_| restyles) {
+                decorated.push_str(&hl.sgr);
+            }
+            active = hl;
+            decorated.push(c);
+        }
+
+        if active.is_some() || !state.is_empty() {
+            decorated.push_str(&format!("{}", termion::style::Reset));
+        }
+        decorated
+    }
+}
+
+/// Apply the SGR sequence to the SGR state.
+/// The state is cleared if the sequence resets all attributes.
+fn apply_sgr(state: &mut String, sgr: &str) {
+    let params = &sgr[2..sgr.len() - 1];
+    let first = params.split(';').next().unwrap_or("");
+    if first.is_empty() || first == "0" {
+        state.clear();
+        if params.is_empty() || params == "0" {
+            return;
+        }
+    }
+    state.push_str(sgr);
+}
+
+/// Return the line as shown on the pane: tabs are expanded,
+/// and the control chars and invalid bytes are replaced with their markers.
+/// SGR sequences are taken out of the text if raw control chars mode is enabled.
+///
+/// Search patterns and filters are matched with the text of it,
+/// so that the matches are found, counted and highlighted at the same places.
+pub fn expand_line(raw: &str, tab_width: usize, raw_control_chars: bool) -> SgrText {
+    if raw_control_chars {
+        SgrText::parse(raw).expand_tab(tab_width)
+    } else {
+        SgrText {
+            text: raw.to_owned(),
+            sgrs: vec![],
+        }
+        .expand_tab(tab_width)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let t = SgrText::parse("\x1b[31mred\x1b[0m \x1b[1;32mgreen\x1b[m");
+        assert_eq!(t.text, "red green");
+        assert_eq!(
+            t.sgrs,
+            vec![
+                (0, "\x1b[31m".to_owned()),
+                (3, "\x1b[0m".to_owned()),
+                (4, "\x1b[1;32m".to_owned()),
+                (9, "\x1b[m".to_owned()),
+            ]
+        );
+
+        // the other sequences are dropped
+        let t = SgrText::parse("a\x1b[Kb\x1b]8;;http://x\x1b\\c\x1b]0;title\x07d\x1b=e");
+        assert_eq!(t.text, "abcde");
+        assert!(t.sgrs.is_empty());
+
+        assert_eq!(SgrText::parse("\x1b[33mあい\x1b[0mう").text, "あいう");
+        assert_eq!(SgrText::parse("abc\x1b").text, "abc");
+    }
+
+    #[test]
+    fn test_expand_tab() {
+        let t = SgrText::parse("a\t\x1b[31mb\tc\x1b[0m").expand_tab(4);
+        assert_eq!(t.text, "a   b   c");
+        assert_eq!(
+            t.sgrs,
+            vec![(4, "\x1b[31m".to_owned()), (9, "\x1b[0m".to_owned())]
+        );
+
+        // the markers of control chars are styled
+        let t = SgrText::parse("\x01\t\x1b[31mb\x7f").expand_tab(4);
+        assert_eq!(t.text, "^A  b^?");
+        assert_eq!(
+            t.sgrs,
+            vec![
+                (0, "\x1b[7m".to_owned()),
+                (2, "\x1b[27m".to_owned()),
+                (4, "\x1b[31m".to_owned()),
+                (5, "\x1b[7m".to_owned()),
+                (7, "\x1b[27m".to_owned()),
+            ]
+        );
+        assert_eq!(
+            t.decorate((0, 7), &[]),
+            "\x1b[7m^A\x1b[27m  \x1b[31mb\x1b[7m^?\x1b[m"
+        );
+    }
+
+    #[test]
+    fn test_expand_line() {
+        let raw = "\x01\t\x1b[31mb\x1b[0m";
+        assert_eq!(expand_line(raw, 4, true).text, "^A  b");
+        assert_eq!(expand_line(raw, 4, false).text, "^A  ^[[31mb^[[0m");
+        assert!(expand_line(raw, 4, false)
+            .sgrs
+            .iter()
+            .all(|(_, sgr)| { sgr == printable::MARKER_SGR || sgr == printable::MARKER_END_SGR }));
+    }
+
+    #[test]
+    fn test_decorate() {
+        let t = SgrText::parse("ab\x1b[31mcd\x1b[0mef");
+        // whole text
+        assert_eq!(t.decorate((0, 6), &[]), "ab\x1b[31mcd\x1b[0mef");
+        // carry the state over the trimmed head
+        assert_eq!(t.decorate((3, 6), &[]), "\x1b[31md\x1b[0mef");
+        assert_eq!(t.decorate((3, 4), &[]), "\x1b[31md\x1b[m");
+        // highlight over colored text restores the color
+        assert_eq!(
+            t.decorate((0, 6), &[Highlight::new(1, 3, "\x1b[7m")]),
+            "a\x1b[7mb\x1b[31m\x1b[7mc\x1b[m\x1b[31md\x1b[0mef"
+        );
+        // adjacent highlights of different styles
+        assert_eq!(
+            t.decorate(
+                (0, 6),
+                &[
+                    Highlight::new(0, 1, "\x1b[7m"),
+                    Highlight::new(1, 2, "\x1b[43m")
+                ]
+            ),
+            "\x1b[7ma\x1b[m\x1b[43mb\x1b[m\x1b[31mcd\x1b[0mef"
+        );
+    }
+
+    #[test]
+    fn test_merge_highlights() {
+        let search = vec![Highlight::new(2, 4, "s"), Highlight::new(8, 9, "s")];
+        let red = vec![Highlight::new(0, 3, "r"), Highlight::new(6, 12, "r")];
+        let blue = vec![Highlight::new(0, 14, "b")];
+        assert_eq!(
+            merge_highlights(vec![search, vec![], red, blue]),
+            vec![
+                Highlight::new(0, 2, "r"),
+                Highlight::new(2, 4, "s"),
+                Highlight::new(4, 6, "b"),
+                Highlight::new(6, 8, "r"),
+                Highlight::new(8, 9, "s"),
+                Highlight::new(9, 12, "r"),
+                Highlight::new(12, 14, "b"),
+            ]
+        );
+        assert!(merge_highlights(vec![]).is_empty());
+    }
+
+    #[test]
+    fn test_apply_sgr() {
+        let mut state = String::new();
+        apply_sgr(&mut state, "\x1b[1m");
+        apply_sgr(&mut state, "\x1b[31m");
+        assert_eq!(state, "\x1b[1m\x1b[31m");
+        apply_sgr(&mut state, "\x1b[0;32m");
+        assert_eq!(state, "\x1b[0;32m");
+        apply_sgr(&mut state, "\x1b[m");
+        assert_eq!(state, "");
+    }
+}
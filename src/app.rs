@@ -1,17 +1,34 @@
+use nix::sys::signal::{SigSet, Signal};
 use std::cell::RefCell;
-use std::fs::File;
-use std::io::{self, BufRead, BufReader, Cursor, Read, Seek, SeekFrom};
+use std::cmp;
+use std::collections::{HashMap, VecDeque};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
 use std::os::unix::io::AsRawFd;
+use std::path::Path;
 use std::rc::Rc;
-use std::sync::mpsc;
+use std::sync::{mpsc, Arc, Mutex};
 use std::thread::spawn;
+use std::time::{Duration, Instant};
+use unicode_width::UnicodeWidthChar;
 
 use crate::{
+    ansi, csi,
     event::PeepEvent,
     filewatch::{self, FileWatch},
-    keybind,
+    filter::{Filter, FilteredSource},
+    hexdump::{self, HexSource},
+    highlight::HighlightSet,
+    history::{self, History, HISTORY_SIZE},
+    input::{self, KeyDecoder},
+    keybind::{self, Key},
+    linesource::{FileSource, LineSource, MergedSource, SourceChange},
+    log::{Level, LogParser},
+    matchcount::MatchCounter,
     pane::{Pane, ScrollStep},
-    search,
+    printable,
+    search::{CaseMode, ModalSearcher, Search, SearchMode},
+    syntax::{Language, SyntaxHighlighter},
     term::{self, Block},
 };
 
@@ -20,31 +37,67 @@ const DEFAULT_TAB_WIDTH: u16 = 4;
 
 const FOLLOWING_MESSAGE: &str = "\x1b[7mwaiting for data... (press 'F' to abort)\x1b[0m";
 const FOLLOWING_HL_MESSAGE: &str = "\x1b[7mwaiting for data... \x1b[0m:";
+const TRUNCATED_MESSAGE: &str = "\x1b[7mfile truncated\x1b[0m";
+const REOPENED_MESSAGE: &str = "\x1b[7mfile replaced, reopened\x1b[0m";
+const NO_MATCH_MESSAGE: &str = "\x1b[31mno match\x1b[0m";
+const BINARY_PROMPT: &str = "binary file, show anyway? (y/n) ";
+/// Number of lines at the head of the file that are checked whether it is binary.
+const BINARY_CHECK_LINES: usize = 64;
+const HIGHLIGHT_PROMPT: &str = "Highlight: ";
+const OFFSET_PROMPT: &str = "Offset: ";
+const NOT_IN_HEX_VIEW_MESSAGE: &str = "not available in the hex view";
 const DEFAULT_POLL_TIMEOUT_MS: u64 = 200;
+const SEARCH_CHUNK_LINES: usize = 4096;
+const CURSOR_REPORT_TIMEOUT_MS: i32 = 500;
+/// Time spent on counting matches between checks of events
+const MATCH_COUNT_SLICE_MS: u64 = 50;
+/// Name of the mark of the position before the last jump
+const PREVIOUS_MARK: char = '\'';
 
 pub struct KeyEventHandler<'a> {
-    istream: &'a mut dyn Read,
+    istream: &'a mut File,
     parser: &'a mut dyn keybind::KeyParser,
+    decoder: KeyDecoder,
+    keys: VecDeque<Key>,
 }
 
 impl<'a> KeyEventHandler<'a> {
-    pub fn new(istream: &'a mut dyn Read, parser: &'a mut dyn keybind::KeyParser) -> Self {
-        KeyEventHandler { istream, parser }
+    pub fn new(istream: &'a mut File, parser: &'a mut dyn keybind::KeyParser) -> Self {
+        KeyEventHandler {
+            istream,
+            parser,
+            decoder: KeyDecoder::new(),
+            keys: VecDeque::new(),
+        }
     }
 
     pub fn read(&mut self) -> Option<PeepEvent> {
-        for b in self.istream.bytes().filter_map(|v| v.ok()) {
-            let v = self.parser.parse(b as char);
-            if v.is_some() {
-                return v;
+        let mut buf = [0u8; 1];
+        loop {
+            while let Some(key) = self.keys.pop_front() {
+                let v = self.parser.parse(key);
+                if v.is_some() {
+                    return v;
+                }
+            }
+            if self.decoder.is_pending()
+                && !input::wait_readable(self.istream.as_raw_fd(), input::ESC_TIMEOUT_MS)
+            {
+                self.keys.extend(self.decoder.flush());
+                continue;
+            }
+            match self.istream.read(&mut buf) {
+                Ok(1) => self.keys.extend(self.decoder.feed(buf[0])),
+                _ => return None,
             }
         }
-        None
     }
 }
 
 struct PipeReader {
     end_with_crlf: bool,
+    // bytes of the char split at the end of the last read
+    pending: Vec<u8>,
 }
 
 impl Default for PipeReader {
@@ -57,6 +110,7 @@ impl PipeReader {
     pub fn new() -> Self {
         Self {
             end_with_crlf: true,
+            pending: Vec::new(),
         }
     }
 
@@ -97,28 +151,12 @@ impl PipeReader {
             tmo = DEFAULT_POLL_TIMEOUT_MS;
             while let Ok(cap) = stdinlock.read(&mut buf) {
                 if cap == 0 {
+                    // EOF: the split char is never completed
+                    let rest = std::mem::take(&mut self.pending);
+                    self.push_line(linebuf, printable::decode_lossy(&rest));
                     break;
                 }
-
-                let mut cursor = Cursor::new(&buf[..cap]);
-                loop {
-                    let mut line = String::new();
-                    if let Ok(n) = cursor.read_line(&mut line) {
-                        if n == 0 {
-                            break;
-                        }
-                        let is_chmoped = PipeReader::chomp(&mut line);
-
-                        if !self.end_with_crlf && linebuf.last_mut().is_some() {
-                            linebuf.last_mut().unwrap().push_str(&line);
-                        } else {
-                            linebuf.push(line);
-                        }
-                        self.end_with_crlf = is_chmoped;
-                    } else {
-                        break;
-                    }
-                }
+                self.push_bytes(linebuf, &buf[..cap]);
             }
             if ready.unwrap() {
                 // is_hup()?
@@ -129,20 +167,158 @@ impl PipeReader {
         stdin.blocking();
         Ok(())
     }
+
+    /// Split the bytes into lines and push them to the buffer.
+    /// The bytes of the char split at the end are kept until the next bytes come.
+    fn push_bytes(&mut self, linebuf: &mut Vec<String>, bytes: &[u8]) {
+        let mut data = std::mem::take(&mut self.pending);
+        data.extend_from_slice(bytes);
+        let complete = data.len() - printable::incomplete_tail_len(&data);
+        self.pending = data.split_off(complete);
+
+        for raw in data.split_inclusive(|&b| b == b'\n') {
+            self.push_line(linebuf, printable::decode_lossy(raw));
+        }
+    }
+
+    /// Push the line, or append it to the last line if that is not terminated yet.
+    fn push_line(&mut self, linebuf: &mut Vec<String>, mut line: String) {
+        if line.is_empty() {
+            return;
+        }
+        let is_chmoped = PipeReader::chomp(&mut line);
+
+        match linebuf.last_mut() {
+            Some(last) if !self.end_with_crlf => {
+                last.push_str(&line);
+                // CR that came before its LF
+                if is_chmoped && line.is_empty() && last.ends_with('\r') {
+                    last.pop();
+                }
+            }
+            _ => linebuf.push(line),
+        }
+        self.end_with_crlf = is_chmoped;
+    }
+}
+
+/// Position marked by `m`.
+#[derive(Clone, Copy, Debug)]
+struct Mark {
+    doc: usize,
+    x: usize,
+    // line of the file, not of the filtered view
+    line: usize,
+}
+
+/// Per-file state kept while another file is shown.
+struct Document {
+    file_path: String,
+    // None until the file is shown at first
+    linebuf: Option<Rc<RefCell<dyn LineSource>>>,
+    position: (usize, usize),
+    pattern: String,
+    // number of lines already appended to the merged view
+    followed: usize,
+}
+
+impl Document {
+    fn new(file_path: &str) -> Self {
+        Self {
+            file_path: file_path.to_owned(),
+            linebuf: None,
+            position: (0, 0),
+            pattern: String::new(),
+            followed: 0,
+        }
+    }
+
+    /// Keep the state of the file while another file is shown.
+    fn save(
+        &mut self,
+        linebuf: Rc<RefCell<dyn LineSource>>,
+        position: (usize, usize),
+        pattern: &str,
+    ) {
+        self.linebuf = Some(linebuf);
+        self.position = position;
+        self.pattern = pattern.to_owned();
+    }
+
+    /// Return the position and the search pattern to show the file again.
+    fn state(&self) -> ((usize, usize), &str) {
+        (self.position, &self.pattern)
+    }
+}
+
+/// Return the error text styled for the message bar.
+fn error_style(e: &str) -> String {
+    format!("\x1b[31m{}\x1b[0m", e)
+}
+
+/// Return the short tag of the file for the merged view.
+fn source_tag(file_path: &str) -> String {
+    const MAX_TAG_WIDTH: usize = 8;
+
+    let name = if file_path == "-" {
+        "stdin"
+    } else {
+        Path::new(file_path)
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or(file_path)
+    };
+    let mut tag = String::new();
+    let mut width = 0;
+    for c in name.chars() {
+        width += c.width().unwrap_or(0);
+        if width > MAX_TAG_WIDTH {
+            break;
+        }
+        tag.push(c);
+    }
+    tag
 }
 
 pub struct App {
     pub show_linenumber: bool,
     pub nlines: u16,
-    pub start_line: u16,
+    pub start_line: usize,
     pub follow_mode: bool,
     pub tab_width: u16,
     pub wraps_line: bool,
+    pub raw_control_chars: bool,
+    pub mouse: bool,
+    pub case_mode: CaseMode,
+    pub fixed_string: bool,
+    pub keymap: Option<HashMap<Vec<Key>, PeepEvent>>,
+    /// Highlight patterns given at startup as `PATTERN[=COLOR]`
+    pub highlights: Vec<String>,
+    /// Language of the syntax highlighting, "auto" by the file extension or "none"
+    pub language: Option<String>,
+    /// Color the levels and the timestamps of log lines
+    pub log: bool,
     typing_word: Option<String>,
+    // error of the pattern being typed
+    pattern_error: Option<String>,
+    source_notice: Option<&'static str>,
     file_path: String,
-    seek_pos: u64,
-    searcher: Rc<RefCell<dyn search::Search>>,
-    linebuf: Rc<RefCell<Vec<String>>>,
+    documents: Vec<Document>,
+    cur_doc: usize,
+    merged: Option<Rc<RefCell<MergedSource>>>,
+    filter: Option<Rc<RefCell<FilteredSource>>>,
+    // hex dump of the base source while the hex view is shown
+    hexdump: Option<Rc<RefCell<HexSource>>>,
+    searcher: Rc<RefCell<ModalSearcher>>,
+    highlight_set: Rc<RefCell<HighlightSet>>,
+    // search history shared with the key reading thread
+    history: Arc<Mutex<History>>,
+    marks: HashMap<char, Mark>,
+    counter: MatchCounter,
+    // the message bar shows the status of search matches
+    shows_match_status: bool,
+    linebuf: Rc<RefCell<dyn LineSource>>,
+    pipebuf: Rc<RefCell<Vec<String>>>,
     pipereader: PipeReader,
     // termios parameter moved from KeyEventHandler to App to detect Drop App.
     term_restorer: Option<term::TermAttrRestorer>,
@@ -150,6 +326,11 @@ pub struct App {
 
 impl Drop for App {
     fn drop(&mut self) {
+        if self.mouse {
+            if let Ok(mut ftty) = OpenOptions::new().write(true).open("/dev/tty") {
+                let _ = write!(ftty, "{}", csi::mouse::DisableReporting);
+            }
+        }
         if let Some(ref tr) = self.term_restorer {
             // Prepare key input setting
             let ftty = File::open("/dev/tty").unwrap();
@@ -166,12 +347,6 @@ impl Default for App {
 
 impl App {
     pub fn new() -> Self {
-        // Prepare key input setting
-        let ftty = File::open("/dev/tty").unwrap();
-        let term_restorer = term::TermAttrSetter::new(ftty.as_raw_fd())
-            .lflag(0, term::ICANON | term::ECHO)
-            .set();
-
         App {
             show_linenumber: false,
             nlines: DEFAULT_PANE_HEIGHT,
@@ -179,18 +354,50 @@ impl App {
             follow_mode: false,
             tab_width: DEFAULT_TAB_WIDTH,
             wraps_line: false,
+            raw_control_chars: false,
+            mouse: false,
+            case_mode: CaseMode::Sensitive,
+            fixed_string: false,
+            keymap: None,
+            highlights: Vec::new(),
+            language: None,
+            log: false,
             typing_word: None,
+            pattern_error: None,
+            source_notice: None,
             file_path: String::new(),
-            seek_pos: 0,
-            searcher: Rc::new(RefCell::new(search::PlaneSearcher::new())),
-            linebuf: Rc::new(RefCell::new(Vec::new())),
+            documents: Vec::new(),
+            cur_doc: 0,
+            merged: None,
+            filter: None,
+            hexdump: None,
+            searcher: Rc::new(RefCell::new(ModalSearcher::new())),
+            highlight_set: Rc::new(RefCell::new(HighlightSet::new())),
+            history: Arc::new(Mutex::new(History::new(HISTORY_SIZE))),
+            marks: HashMap::new(),
+            counter: MatchCounter::new(DEFAULT_TAB_WIDTH as usize, false),
+            shows_match_status: false,
+            linebuf: Rc::new(RefCell::new(Vec::<String>::new())),
+            pipebuf: Rc::new(RefCell::new(Vec::new())),
             pipereader: Default::default(),
-            term_restorer: Some(term_restorer),
+            term_restorer: None,
         }
     }
 
     fn read_buffer(&mut self, tmo_ms: u64) -> io::Result<()> {
-        if self.file_path == "-" {
+        let (file_path, linebuf) = (self.file_path.clone(), self.linebuf.clone());
+        let change = self.update_source(&file_path, &linebuf, tmo_ms)?;
+        self.update_filter(change)
+    }
+
+    /// Pick up the appended data of the source.
+    fn update_source(
+        &mut self,
+        file_path: &str,
+        linebuf: &Rc<RefCell<dyn LineSource>>,
+        tmo_ms: u64,
+    ) -> io::Result<SourceChange> {
+        let change = if file_path == "-" {
             // read from stdin if pipe
             if termion::is_tty(&io::stdin()) {
                 // stdin is tty. not pipe.
@@ -200,35 +407,172 @@ impl App {
                 ));
             }
             self.pipereader
-                .read(&mut self.linebuf.borrow_mut(), tmo_ms)?;
-        } else if let Ok(mut file) = File::open(&self.file_path) {
-            // read from file
-            self.seek_pos = file.seek(SeekFrom::Start(self.seek_pos))?;
-            let bufreader = BufReader::new(file);
-            for line in bufreader.lines() {
-                // +1 is LR length
-                let v = line?;
-                self.seek_pos += v.as_bytes().len() as u64 + 1;
-                self.linebuf.borrow_mut().push(v);
-            }
+                .read(&mut self.pipebuf.borrow_mut(), tmo_ms)?;
+            SourceChange::Appended
         } else {
-            return Err(io::Error::new(
+            // pick up appended lines of file
+            linebuf.borrow_mut().update()?
+        };
+        match change {
+            SourceChange::Truncated => self.source_notice = Some(TRUNCATED_MESSAGE),
+            SourceChange::Reopened => self.source_notice = Some(REOPENED_MESSAGE),
+            SourceChange::Appended => {}
+        }
+        Ok(change)
+    }
+
+    /// Open the source of the path and read lines enough for the first drawing.
+    fn open_source(&mut self, file_path: &str) -> io::Result<Rc<RefCell<dyn LineSource>>> {
+        if file_path == "-" {
+            let linebuf: Rc<RefCell<dyn LineSource>> = self.pipebuf.clone();
+            self.update_source(file_path, &linebuf, 1000)?;
+            return Ok(linebuf);
+        }
+
+        let src = FileSource::open(file_path).map_err(|_| {
+            io::Error::new(
                 io::ErrorKind::NotFound,
-                format!("{} is not found", self.file_path),
-            ));
+                format!("{} is not found", file_path),
+            )
+        })?;
+        if self.follow_mode {
+            src.sync();
+        } else {
+            src.wait_for_lines(self.start_line + self.nlines as usize);
+        }
+        Ok(Rc::new(RefCell::new(src)))
+    }
+
+    /// Open the current file.
+    fn open_buffer(&mut self) -> io::Result<()> {
+        let file_path = self.file_path.clone();
+        self.linebuf = self.open_source(&file_path)?;
+        Ok(())
+    }
+
+    /// Return the source of the document. The file is opened if it is not yet.
+    fn document_source(&mut self, index: usize) -> io::Result<Rc<RefCell<dyn LineSource>>> {
+        if index == self.cur_doc {
+            return Ok(self.linebuf.clone());
+        }
+        if let Some(ref linebuf) = self.documents[index].linebuf {
+            return Ok(linebuf.clone());
+        }
+        let file_path = self.documents[index].file_path.clone();
+        let linebuf = self.open_source(&file_path)?;
+        self.documents[index].linebuf = Some(linebuf.clone());
+        Ok(linebuf)
+    }
+
+    /// Return whether the follow mode shows the merged view of multiple files.
+    fn is_multiplexed(&self) -> bool {
+        self.documents.len() > 1
+    }
+
+    /// Show the merged view of all files.
+    /// At the first time, the view begins with the tail of each file.
+    fn start_merged_view(&mut self, pane: &mut Pane) -> io::Result<()> {
+        if self.merged.is_none() {
+            let tags = self
+                .documents
+                .iter()
+                .map(|d| source_tag(&d.file_path))
+                .collect();
+            self.merged = Some(Rc::new(RefCell::new(MergedSource::new(tags))));
+            for i in 0..self.documents.len() {
+                if let Ok(linebuf) = self.document_source(i) {
+                    linebuf.borrow().sync();
+                    let len = linebuf.borrow().len();
+                    self.documents[i].followed = len.saturating_sub(self.nlines as usize);
+                }
+            }
+        }
+        for i in 0..self.documents.len() {
+            self.pull_document(i)?;
+        }
+        if let Some(merged) = self.merged.clone() {
+            self.load_view(pane, merged);
         }
         Ok(())
     }
 
-    pub fn run(&mut self, path: &str) -> io::Result<()> {
-        self.file_path = path.to_owned();
-        self.read_buffer(1000)?;
+    /// Append the new lines of the document to the merged view.
+    fn pull_document(&mut self, index: usize) -> io::Result<()> {
+        let merged = match self.merged {
+            Some(ref merged) => merged.clone(),
+            None => return Ok(()),
+        };
+        let linebuf = match self.document_source(index) {
+            Ok(linebuf) => linebuf,
+            // the file doesn't exist
+            Err(_) => return Ok(()),
+        };
+        let file_path = self.documents[index].file_path.clone();
+        if self.update_source(&file_path, &linebuf, DEFAULT_POLL_TIMEOUT_MS)?
+            != SourceChange::Appended
+        {
+            self.documents[index].followed = 0;
+        }
+        let len = linebuf.borrow().len();
+        for line in linebuf.borrow().lines(self.documents[index].followed..len) {
+            merged.borrow_mut().push(index, line);
+        }
+        self.documents[index].followed = len;
+        // the lines of the documents are only appended to the merged view
+        self.update_filter(SourceChange::Appended)
+    }
+
+    pub fn run(&mut self, paths: &[String]) -> io::Result<()> {
+        // Prepare key input setting
+        let ftty = File::open("/dev/tty")?;
+        self.term_restorer = Some(
+            term::TermAttrSetter::new(ftty.as_raw_fd())
+                .lflag(0, term::ICANON | term::ECHO)
+                .set(),
+        );
+
+        // SIGWINCH is blocked before any thread is spawned (e.g. the indexing thread of files),
+        // so that only the resize thread receives it.
+        let mut winch = SigSet::empty();
+        winch.add(Signal::SIGWINCH);
+        winch.thread_block().map_err(io::Error::from)?;
+
+        let mode = SearchMode {
+            case: self.case_mode,
+            fixed_string: self.fixed_string,
+        };
+        for text in self.highlights.iter() {
+            self.highlight_set
+                .borrow_mut()
+                .add(text, mode)
+                .map_err(|e| io::Error::new(e.kind(), format!("--hl {}: {}", text, e)))?;
+        }
+
+        if let Some(name) = self.language.as_deref() {
+            if name != "auto" && name != "none" {
+                Language::from_name(name)?;
+            }
+        }
+
+        self.documents = paths.iter().map(|p| Document::new(p)).collect();
+        self.file_path = paths[0].clone();
+        self.open_buffer()?;
 
         let writer = io::stdout();
         let writer = writer.lock();
 
         let (event_sender, event_receiver) = mpsc::channel();
 
+        let resize_sender = event_sender.clone();
+        // Resize thread
+        let _resizethread = spawn(move || {
+            while winch.wait().is_ok() {
+                if resize_sender.send(PeepEvent::Resize).is_err() {
+                    break;
+                }
+            }
+        });
+
         let sig_sender = event_sender.clone();
         // Ctrl-C handler
         ctrlc::set_handler(move || {
@@ -237,35 +581,70 @@ impl App {
         })
         .expect("Error setting ctrl-c handler");
 
-        self.searcher = Rc::new(RefCell::new(search::RegexSearcher::new("")));
+        let mut searcher = ModalSearcher::new();
+        // the pattern is empty, so that the modes are always set
+        let _ = searcher.set_case_mode(self.case_mode);
+        let _ = searcher.set_fixed_string(self.fixed_string);
+        self.searcher = Rc::new(RefCell::new(searcher));
+        self.counter = MatchCounter::new(self.tab_width as usize, self.raw_control_chars);
 
         let mut pane = Pane::new(Box::new(RefCell::new(writer)));
         pane.load(self.linebuf.clone());
         pane.set_highlight_searcher(self.searcher.clone());
+        pane.set_highlight_set(self.highlight_set.clone());
+        pane.set_syntax(self.syntax_of(&self.file_path));
+        if self.log {
+            pane.set_log(Some(LogParser::new()));
+        }
         pane.show_line_number(self.show_linenumber);
         pane.set_tab_width(self.tab_width);
         pane.set_wrap(self.wraps_line);
+        pane.set_raw_control_chars(self.raw_control_chars);
         pane.set_height(self.nlines)?;
+        if pane.is_stdout_tty() && !self.confirm_binary(&mut pane)? {
+            pane.quit();
+            return Ok(());
+        }
         if self.follow_mode {
+            if self.is_multiplexed() {
+                self.start_merged_view(&mut pane)?;
+            }
             pane.goto_bottom_of_lines()?;
         }
         if self.start_line > 0 {
             pane.goto_absolute_line(self.start_line - 1)?;
         }
-        pane.set_message(self.mode_default_message());
+        pane.set_message(self.mode_default_message().or_else(|| self.file_message()));
         pane.refresh()?;
 
         // if stdout points pipe or redirect,
         // peep exits immediately upon output.
         if !pane.is_stdout_tty() {
+            // nothing to restore
+            self.mouse = false;
             return Ok(());
         }
+        if self.mouse {
+            self.enable_mouse_reporting(&mut pane)?;
+        }
 
         let key_sender = event_sender.clone();
+        let keymap = self.keymap.take();
+        // a broken history file doesn't prevent peep from running
+        if let Some(history) =
+            history::history_file().and_then(|path| History::load(&path, HISTORY_SIZE).ok())
+        {
+            *self.history.lock().unwrap() = history;
+        }
+        let history = self.history.clone();
         // Key reading thread
         let _keythread = spawn(move || {
             let mut keyin = File::open("/dev/tty").unwrap();
-            let mut kb = keybind::default::KeyBind::new();
+            let mut kb = match keymap {
+                Some(cmap) => keybind::default::KeyBind::with_command_table(cmap),
+                None => keybind::default::KeyBind::new(),
+            };
+            kb.set_history(history);
             let mut keh = KeyEventHandler::new(&mut keyin, &mut kb);
 
             loop {
@@ -275,13 +654,29 @@ impl App {
             }
         });
 
-        // spawn inotifier thread for following mode
-        let file_path_to_watch = self.file_path.clone();
-        let _fwthread = spawn(move || filewatch::file_watcher(&file_path_to_watch, &event_sender));
+        // spawn inotifier thread of each file for following mode
+        for (i, file_path_to_watch) in paths.iter().cloned().enumerate() {
+            let fw_sender = event_sender.clone();
+            let _fwthread =
+                spawn(move || filewatch::file_watcher(&file_path_to_watch, i, &fw_sender));
+        }
 
         // app loop
         loop {
-            if let Ok(event) = event_receiver.recv() {
+            let event = if self.is_counting_matches() {
+                match event_receiver.try_recv() {
+                    Ok(event) => Ok(event),
+                    Err(mpsc::TryRecvError::Empty) => {
+                        // count matches while no event arrives
+                        self.count_matches_for_a_while(&mut pane)?;
+                        continue;
+                    }
+                    Err(mpsc::TryRecvError::Disconnected) => Err(mpsc::RecvError),
+                }
+            } else {
+                event_receiver.recv()
+            };
+            if let Ok(event) = event {
                 if !self.follow_mode {
                     self.handle_normal(&event, &mut pane)?;
                 } else {
@@ -299,13 +694,633 @@ impl App {
         Ok(())
     }
 
+    /// Ask whether the file is shown if it looks binary, and return the answer.
+    /// The key is read here because the key reading thread is not started yet.
+    fn confirm_binary(&self, pane: &mut Pane) -> io::Result<bool> {
+        let head = self.linebuf.borrow().lines(0..BINARY_CHECK_LINES).concat();
+        if !printable::is_binary(&head) {
+            return Ok(true);
+        }
+        pane.load(Rc::new(RefCell::new(Vec::<String>::new())));
+        pane.set_message(Some(BINARY_PROMPT.to_owned()));
+        pane.refresh()?;
+
+        let mut key = [0u8; 1];
+        File::open("/dev/tty")?.read_exact(&mut key)?;
+        pane.load(self.view());
+        Ok(key[0] == b'y' || key[0] == b'Y')
+    }
+
+    /// Start the mouse reporting.
+    /// The message bar position is needed to find clicks on it.
+    fn enable_mouse_reporting(&mut self, pane: &mut Pane) -> io::Result<()> {
+        let mut ftty = OpenOptions::new().write(true).open("/dev/tty")?;
+        write!(ftty, "{}", csi::mouse::EnableReporting)?;
+        // the cursor is on the message bar after refresh
+        if let Ok((_, row)) = term::dev_tty_cursor_position(CURSOR_REPORT_TIMEOUT_MS) {
+            pane.locate_message_bar(row)?;
+        }
+        Ok(())
+    }
+
+    /// Return the message of the current file if multiple files are given.
+    fn file_message(&self) -> Option<String> {
+        if self.documents.len() < 2 {
+            return None;
+        }
+        let name = if self.file_path == "-" {
+            "(standard input)"
+        } else {
+            &self.file_path
+        };
+        Some(format!(
+            "{} (file {} of {})",
+            name,
+            self.cur_doc + 1,
+            self.documents.len()
+        ))
+    }
+
+    /// Switch the shown file to the document of the index.
+    /// The position and the search pattern of the current file are kept.
+    fn switch_document(&mut self, index: usize, pane: &mut Pane) -> io::Result<()> {
+        if index == self.cur_doc || index >= self.documents.len() {
+            pane.set_message(self.file_message());
+            return Ok(());
+        }
+
+        let position = match self.swap_document(index, pane.position()) {
+            Ok(position) => position,
+            Err(e) => {
+                // stay on the current file
+                pane.set_message(Some(e.to_string()));
+                return Ok(());
+            }
+        };
+
+        self.load_view(pane, self.linebuf.clone());
+        if self.hexdump.is_none() {
+            pane.set_syntax(self.syntax_of(&self.file_path));
+        }
+        pane.goto_absolute_line(position.1)?;
+        pane.goto_absolute_horizontal_offset(position.0)?;
+        pane.show_highlight(!self.searcher.borrow().as_str().is_empty());
+        pane.set_message(self.file_message());
+        self.update_mark_gutter(pane);
+        Ok(())
+    }
+
+    /// Save the source, the position and the search pattern of the current file,
+    /// and restore the source and the search pattern of the file of the index.
+    /// Return the position of the file to show it on the pane.
+    fn swap_document(
+        &mut self,
+        index: usize,
+        position: (usize, usize),
+    ) -> io::Result<(usize, usize)> {
+        let linebuf = self.document_source(index)?;
+        let pattern = self.searcher.borrow().as_str().to_owned();
+        self.documents[self.cur_doc].save(self.linebuf.clone(), position, &pattern);
+
+        self.cur_doc = index;
+        self.file_path = self.documents[index].file_path.clone();
+        self.linebuf = linebuf;
+        let (position, pattern) = self.documents[index].state();
+        let _ = self.searcher.borrow_mut().set_pattern(pattern);
+        Ok(position)
+    }
+
+    /// Return the syntax highlighter of the file.
+    fn syntax_of(&self, file_path: &str) -> Option<SyntaxHighlighter> {
+        let language = match self.language.as_deref() {
+            Some("none") => None,
+            None | Some("auto") => Language::from_path(file_path),
+            Some(name) => Language::from_name(name).ok(),
+        };
+        language.map(SyntaxHighlighter::new)
+    }
+
+    /// Return the current position as the mark.
+    fn current_mark(&self, pane: &Pane) -> Mark {
+        let (x, y) = pane.position();
+        Mark {
+            doc: self.cur_doc,
+            x,
+            line: self.view().borrow().origin_line(y),
+        }
+    }
+
+    /// Remember the position before the jump, so that `''` goes back to it.
+    fn remember_jump(&mut self, pane: &Pane) {
+        if self.hexdump.is_some() {
+            // marks are the lines of the file
+            return;
+        }
+        let mark = self.current_mark(pane);
+        self.marks.insert(PREVIOUS_MARK, mark);
+    }
+
+    /// Mark the current position with the letter.
+    fn set_mark(&mut self, name: char, pane: &mut Pane) {
+        if self.hexdump.is_some() {
+            pane.set_message(Some(error_style(NOT_IN_HEX_VIEW_MESSAGE)));
+            return;
+        }
+        if !name.is_ascii_alphabetic() {
+            pane.set_message(Some(error_style(&format!("invalid mark: {}", name))));
+            return;
+        }
+        let mark = self.current_mark(pane);
+        self.marks.insert(name, mark);
+        self.update_mark_gutter(pane);
+        pane.set_message(Some(format!("Mark {}", name)));
+    }
+
+    /// Go to the marked position. The file of the mark is examined if it is not shown.
+    fn jump_to_mark(&mut self, name: char, pane: &mut Pane) -> io::Result<()> {
+        if self.hexdump.is_some() {
+            pane.set_message(Some(error_style(NOT_IN_HEX_VIEW_MESSAGE)));
+            return Ok(());
+        }
+        let mark = match self.marks.get(&name) {
+            Some(&mark) => mark,
+            None => {
+                pane.set_message(Some(error_style(&format!("mark not set: {}", name))));
+                return Ok(());
+            }
+        };
+        self.remember_jump(pane);
+        if mark.doc != self.cur_doc {
+            self.switch_document(mark.doc, pane)?;
+            if mark.doc != self.cur_doc {
+                // the file can't be opened
+                return Ok(());
+            }
+        }
+        self.view().borrow().sync();
+        let y = match self.filter {
+            Some(ref filter) => filter.borrow().position_of(mark.line),
+            None => mark.line,
+        };
+        pane.goto_absolute_line(y)?;
+        pane.goto_absolute_horizontal_offset(mark.x)?;
+        Ok(())
+    }
+
+    /// Show the marks of the current file on the gutter.
+    /// The hex view shows no marks.
+    fn update_mark_gutter(&self, pane: &mut Pane) {
+        if self.hexdump.is_some() {
+            pane.set_marks(Vec::new());
+            return;
+        }
+        pane.set_marks(
+            self.marks
+                .iter()
+                .filter(|(&name, mark)| name != PREVIOUS_MARK && mark.doc == self.cur_doc)
+                .map(|(&name, mark)| (name, mark.line))
+                .collect(),
+        );
+    }
+
+    /// Return the source shown on the pane.
+    /// It is the hex view if it is on, or the filtered view if filters are applied.
+    fn view(&self) -> Rc<RefCell<dyn LineSource>> {
+        if let Some(ref hexdump) = self.hexdump {
+            return hexdump.clone();
+        }
+        match self.filter {
+            Some(ref filter) => filter.clone(),
+            None => self.base_source(),
+        }
+    }
+
+    /// Return the source that filters are applied to.
+    fn base_source(&self) -> Rc<RefCell<dyn LineSource>> {
+        match self.merged {
+            Some(ref merged) if self.follow_mode => merged.clone(),
+            _ => self.linebuf.clone(),
+        }
+    }
+
+    /// Load the source on the pane through the filters, or as the hex view if it is on.
+    fn load_view(&mut self, pane: &mut Pane, base: Rc<RefCell<dyn LineSource>>) {
+        self.counter.reset();
+        if let Some(ref filter) = self.filter {
+            filter.borrow_mut().set_base(base.clone());
+        }
+        if let Some(ref hexdump) = self.hexdump {
+            hexdump.borrow_mut().set_base(base.clone());
+        }
+        let view: Rc<RefCell<dyn LineSource>> = match (&self.hexdump, &self.filter) {
+            (Some(hexdump), _) => hexdump.clone(),
+            (None, Some(filter)) => filter.clone(),
+            (None, None) => base,
+        };
+        pane.load(view);
+    }
+
+    /// Pick up the lines appended to the base source into the filtered view and the hex view.
+    fn update_filter(&mut self, change: SourceChange) -> io::Result<()> {
+        if let Some(ref filter) = self.filter {
+            if change == SourceChange::Appended {
+                filter.borrow_mut().update()?;
+            } else {
+                // the new source may be already as long as the old one
+                filter.borrow_mut().rescan();
+            }
+        }
+        if let Some(ref hexdump) = self.hexdump {
+            hexdump.borrow_mut().update()?;
+        }
+        if change != SourceChange::Appended {
+            // the counts of the old lines don't tell the new ones
+            self.counter.reset();
+        }
+        if self.shows_match_status {
+            // the last line may be extended, so that it's counted again
+            self.counter
+                .scan(&*self.view().borrow(), &*self.searcher.borrow());
+        }
+        Ok(())
+    }
+
+    /// Toggle the hex view of the base source. The filters are not applied to it.
+    /// The line on the top of the pane is kept, and the search pattern is cleared
+    /// because the text and the bytes are searched with the different patterns.
+    fn toggle_hex_view(&mut self, pane: &mut Pane) -> io::Result<()> {
+        let y = pane.position().1;
+        self.counter.reset();
+        self.typing_word = None;
+        self.pattern_error = None;
+        pane.show_highlight(false);
+        {
+            let mut searcher = self.searcher.borrow_mut();
+            // the empty pattern is valid in both modes
+            let _ = searcher.set_pattern("");
+            let _ = searcher.set_hex(self.hexdump.is_none());
+        }
+
+        match self.hexdump.take() {
+            None => {
+                let origin = self.view().borrow().origin_line(y);
+                let hexdump = Rc::new(RefCell::new(HexSource::new(self.base_source())));
+                let offset = hexdump.borrow().offset_of_base_line(origin);
+                self.hexdump = Some(hexdump.clone());
+                pane.load(hexdump.clone());
+                pane.set_offset_gutter(Some(hexdump::BYTES_PER_LINE));
+                pane.set_syntax(None);
+                pane.set_log(None);
+                let n = hexdump.borrow().line_at_offset(offset).unwrap_or(0);
+                pane.goto_absolute_line(n)?;
+                pane.set_message(Some("Hex view".to_owned()));
+            }
+            Some(hexdump) => {
+                let offset = hexdump.borrow().offset_of_line(y).unwrap_or(0);
+                let origin = hexdump.borrow().base_line_at_offset(offset);
+                pane.load(self.view());
+                pane.set_offset_gutter(None);
+                pane.set_syntax(self.syntax_of(&self.file_path));
+                if self.log {
+                    pane.set_log(Some(LogParser::new()));
+                }
+                let n = match self.filter {
+                    Some(ref filter) => filter.borrow().position_of(origin),
+                    None => origin,
+                };
+                pane.goto_absolute_line(n)?;
+                pane.set_message(Some("Text view".to_owned()));
+            }
+        }
+        self.update_mark_gutter(pane);
+        Ok(())
+    }
+
+    /// Go to the line that has the byte at the offset of the command text.
+    /// The text view needs the raw data of the file to find the line.
+    fn goto_offset(&mut self, text: &str, pane: &mut Pane) -> io::Result<()> {
+        let offset = match hexdump::parse_offset(text) {
+            Ok(offset) => offset,
+            Err(e) => {
+                pane.set_message(Some(format!(
+                    "{}{}: {}",
+                    OFFSET_PROMPT,
+                    text,
+                    error_style(&e.to_string())
+                )));
+                return Ok(());
+            }
+        };
+        let base = self.base_source();
+        if base.borrow().byte_len().is_some_and(|len| offset >= len) {
+            // wait for the rest of the file
+            base.borrow().sync();
+        }
+        let n = match (&self.hexdump, &self.filter) {
+            (Some(hexdump), _) => hexdump.borrow().line_at_offset(offset),
+            (None, Some(filter)) => base
+                .borrow()
+                .line_at_offset(offset)
+                .map(|origin| filter.borrow().position_of(origin)),
+            (None, None) => base.borrow().line_at_offset(offset),
+        };
+        match n {
+            Some(n) => {
+                self.remember_jump(pane);
+                pane.goto_absolute_line(n)?;
+                pane.set_message(None);
+            }
+            None => pane.set_message(Some(error_style(
+                "offsets of the input are only in the hex view",
+            ))),
+        }
+        Ok(())
+    }
+
+    /// Stack the filter of the command text on the view.
+    /// The empty text removes all filters.
+    fn apply_filter(&mut self, text: &str, pane: &mut Pane) -> io::Result<()> {
+        if self.hexdump.is_some() {
+            pane.set_message(Some(format!(
+                "&{}: {}",
+                text,
+                error_style(NOT_IN_HEX_VIEW_MESSAGE)
+            )));
+            return Ok(());
+        }
+        // keep the current line of the file on the top of the pane
+        let origin = self.view().borrow().origin_line(pane.position().1);
+        self.counter.reset();
+
+        if text.is_empty() {
+            self.filter = None;
+            pane.load(self.base_source());
+            pane.goto_absolute_line(origin)?;
+            pane.set_message(Some("Filter off".to_owned()));
+            return Ok(());
+        }
+
+        let filter = match Filter::parse(text, self.searcher.borrow().mode()) {
+            Ok(filter) => filter,
+            Err(e) => {
+                pane.set_message(Some(format!("&{}: {}", text, e)));
+                return Ok(());
+            }
+        };
+        let view = self.filtered_view();
+        view.borrow_mut().push_filter(filter);
+        self.show_filtered_view(view, origin, pane)
+    }
+
+    /// Cycle the minimum log level of the view: DEBUG, INFO, WARN, ERROR and off.
+    fn cycle_log_level(&mut self, pane: &mut Pane) -> io::Result<()> {
+        if self.hexdump.is_some() {
+            pane.set_message(Some(error_style(NOT_IN_HEX_VIEW_MESSAGE)));
+            return Ok(());
+        }
+        let origin = self.view().borrow().origin_line(pane.position().1);
+        self.counter.reset();
+
+        let view = self.filtered_view();
+        let level = Level::cycle(view.borrow().min_level());
+        view.borrow_mut().set_min_level(level);
+        if level.is_none() && view.borrow().filters().is_empty() {
+            self.filter = None;
+            pane.load(self.base_source());
+            pane.goto_absolute_line(origin)?;
+            pane.set_message(Some("Log level filter off".to_owned()));
+            return Ok(());
+        }
+        self.show_filtered_view(view, origin, pane)
+    }
+
+    /// Return the filtered view, creating the one without filters if there is none.
+    fn filtered_view(&mut self) -> Rc<RefCell<FilteredSource>> {
+        match self.filter {
+            Some(ref view) => view.clone(),
+            None => {
+                let view = Rc::new(RefCell::new(FilteredSource::new(
+                    self.base_source(),
+                    self.tab_width as usize,
+                    self.raw_control_chars,
+                )));
+                view.borrow_mut().rescan();
+                self.filter = Some(view.clone());
+                view
+            }
+        }
+    }
+
+    /// Load the filtered view and show its filters.
+    /// The pane goes to the first line at or after the origin line.
+    fn show_filtered_view(
+        &mut self,
+        view: Rc<RefCell<FilteredSource>>,
+        origin: usize,
+        pane: &mut Pane,
+    ) -> io::Result<()> {
+        pane.load(view.clone());
+
+        let view = view.borrow();
+        let n = view.position_of(origin);
+        let mut message = view
+            .filters()
+            .iter()
+            .map(|f| format!("&{}", f.as_str()))
+            .collect::<Vec<_>>();
+        if let Some(level) = view.min_level() {
+            message.push(format!("level>={}", level.as_str()));
+        }
+        if self.follow_mode {
+            pane.goto_bottom_of_lines()?;
+        } else {
+            pane.goto_absolute_line(n)?;
+        }
+        pane.set_message(Some(message.join(" ")));
+        Ok(())
+    }
+
+    /// Update the highlight set with the command text and show the result.
+    /// `PATTERN[=COLOR]` adds the pattern, `-PATTERN` removes it and the empty text removes all.
+    fn apply_highlight(&mut self, text: &str, pane: &mut Pane) {
+        let mut set = self.highlight_set.borrow_mut();
+        let result = if text.is_empty() {
+            set.clear();
+            Ok(())
+        } else if let Some(pat) = text.strip_prefix('-') {
+            if set.remove(pat) {
+                Ok(())
+            } else {
+                Err(io::Error::new(io::ErrorKind::NotFound, "not highlighted"))
+            }
+        } else {
+            set.add(text, self.searcher.borrow().mode())
+        };
+        let message = match result {
+            Err(e) => format!(
+                "{}{}: {}",
+                HIGHLIGHT_PROMPT,
+                text,
+                error_style(&e.to_string())
+            ),
+            Ok(()) if set.is_empty() => "Highlight off".to_owned(),
+            Ok(()) => set
+                .rules()
+                .iter()
+                .map(|r| r.as_str())
+                .collect::<Vec<_>>()
+                .join(" "),
+        };
+        pane.set_message(Some(message));
+    }
+
+    /// Add the accepted search pattern to the history, and save it to the history file.
+    /// The byte patterns of the hex view are not kept.
+    fn save_history(&self) {
+        let pattern = match self.typing_word {
+            Some(ref pattern) if !self.searcher.borrow().is_hex() => pattern,
+            _ => return,
+        };
+        self.history.lock().unwrap().push(pattern);
+        if let Some(path) = history::history_file() {
+            // failure of saving the history is not worth stopping peep
+            let _ = history::append(&path, pattern, HISTORY_SIZE);
+        }
+    }
+
+    /// Set the search pattern, and return whether it is valid.
+    /// The previous pattern is kept if the pattern has an error.
+    fn set_search_pattern(&mut self, pat: &str) -> bool {
+        match self.searcher.borrow_mut().set_pattern(pat) {
+            Ok(()) => {
+                self.pattern_error = None;
+                true
+            }
+            Err(e) => {
+                self.pattern_error = Some(e.to_string());
+                false
+            }
+        }
+    }
+
+    /// Return the message of the pattern error when the pattern is triggered.
+    /// The error is cleared, and the search goes on with the previous pattern.
+    fn pattern_error_message(&mut self) -> Option<String> {
+        let e = self.pattern_error.take()?;
+        let tw = self.typing_word.as_deref().unwrap_or_default();
+        Some(format!("/{}: {}", tw, error_style(&e)))
+    }
+
+    /// Return whether the matches are being counted for the message bar.
+    fn is_counting_matches(&self) -> bool {
+        self.shows_match_status && !self.counter.is_complete(&*self.view().borrow())
+    }
+
+    /// Count the matches for a while, and show the count so far.
+    fn count_matches_for_a_while(&mut self, pane: &mut Pane) -> io::Result<()> {
+        let view = self.view();
+        let until = Instant::now() + Duration::from_millis(MATCH_COUNT_SLICE_MS);
+        while Instant::now() < until && !self.counter.is_complete(&*view.borrow()) {
+            self.counter.scan(&*view.borrow(), &*self.searcher.borrow());
+            if view.borrow().is_loading() && self.counter.scanned() >= view.borrow().len() {
+                // wait for the lines being loaded
+                break;
+            }
+        }
+        pane.set_message(self.search_message(pane));
+        pane.refresh()
+    }
+
+    /// Return the message of the search prompt or the status of the matches.
+    /// The status is shown as "match i of N" or "N matches" and N has "+" while counting.
+    fn search_message(&mut self, pane: &Pane) -> Option<String> {
+        if let (Some(tw), Some(e)) = (&self.typing_word, &self.pattern_error) {
+            return Some(format!("{}  ({})", self.search_prompt(tw), error_style(e)));
+        }
+        if self.searcher.borrow().as_str().is_empty() {
+            return self
+                .typing_word
+                .as_ref()
+                .map(|tw| self.search_prompt(tw))
+                .or_else(|| self.mode_default_message());
+        }
+        self.shows_match_status = true;
+
+        let total = self.counter.total();
+        let counting = if self.counter.is_complete(&*self.view().borrow()) {
+            ""
+        } else {
+            "+"
+        };
+        let index = pane
+            .current_match()
+            .and_then(|(y, nth)| self.counter.index_of(y, nth))
+            .filter(|&i| i < total);
+        let status = match index {
+            _ if total == 0 && counting.is_empty() => NO_MATCH_MESSAGE.to_owned(),
+            Some(i) => format!("match {} of {}{}", i + 1, total, counting),
+            None => format!("{}{} matches", total, counting),
+        };
+        Some(match self.typing_word {
+            Some(ref tw) => format!("{}  ({})", self.search_prompt(tw), status),
+            None => status,
+        })
+    }
+
+    /// Return the search prompt with the active search mode.
+    fn search_prompt(&self, word: &str) -> String {
+        format!("{}/{}", self.searcher.borrow().mode_label(), word)
+    }
+
+    /// Toggle the search mode and return the message of the result.
+    /// The current pattern is searched again with the new mode.
+    fn toggle_search_mode(&mut self, event: &PeepEvent) -> String {
+        self.counter.reset();
+        let mut searcher = self.searcher.borrow_mut();
+        let (result, name, enabled) = match event {
+            PeepEvent::ToggleSmartCase => {
+                let enabled = searcher.case_mode() != CaseMode::Smart;
+                let mode = if enabled {
+                    CaseMode::Smart
+                } else {
+                    CaseMode::Sensitive
+                };
+                (searcher.set_case_mode(mode), "Smart-case", enabled)
+            }
+            PeepEvent::ToggleIgnoreCase => {
+                let enabled = searcher.case_mode() != CaseMode::Insensitive;
+                let mode = if enabled {
+                    CaseMode::Insensitive
+                } else {
+                    CaseMode::Sensitive
+                };
+                (searcher.set_case_mode(mode), "Ignore-case", enabled)
+            }
+            _ => {
+                let enabled = !searcher.is_fixed_string();
+                (searcher.set_fixed_string(enabled), "Fixed-string", enabled)
+            }
+        };
+        match result {
+            Ok(()) => format!("{} search {}", name, if enabled { "on" } else { "off" }),
+            Err(e) => format!("{}: {}", name, e),
+        }
+    }
+
     fn mode_default_message(&self) -> Option<String> {
         if !self.follow_mode {
             // normal mode
             None
         } else if let Some(ref tw) = self.typing_word {
             // follow mode + highlighting
-            Some(format!("{}/{}", FOLLOWING_HL_MESSAGE, tw))
+            Some(format!(
+                "{}{}",
+                FOLLOWING_HL_MESSAGE,
+                self.search_prompt(tw)
+            ))
+        } else if let Some(notice) = self.source_notice {
+            // follow mode + the file was truncated or replaced
+            Some(format!("{} {}", notice, FOLLOWING_MESSAGE))
         } else {
             // follow mode
             Some(FOLLOWING_MESSAGE.to_owned())
@@ -313,6 +1328,10 @@ impl App {
     }
 
     fn handle_normal(&mut self, event: &PeepEvent, pane: &mut Pane) -> io::Result<()> {
+        if !matches!(event, PeepEvent::Resize | PeepEvent::FileUpdated(_)) {
+            // the search events show the status again
+            self.shows_match_status = false;
+        }
         match event {
             &PeepEvent::MoveDown(n) => {
                 pane.scroll_down(&ScrollStep::Char(n))?;
@@ -363,14 +1382,26 @@ impl App {
                 pane.refresh()?;
             }
             PeepEvent::MoveToTopOfLines => {
+                self.remember_jump(pane);
                 pane.goto_top_of_lines()?;
                 pane.refresh()?;
             }
             PeepEvent::MoveToBottomOfLines => {
+                self.remember_jump(pane);
+                self.view().borrow().sync();
                 pane.goto_bottom_of_lines()?;
                 pane.refresh()?;
             }
             &PeepEvent::MoveToLineNumber(n) => {
+                self.remember_jump(pane);
+                if n >= self.view().borrow().len() {
+                    self.view().borrow().sync();
+                }
+                // the line number of the file is shown on the filtered view
+                let n = match self.filter {
+                    Some(ref filter) if self.hexdump.is_none() => filter.borrow().position_of(n),
+                    _ => n,
+                };
                 pane.goto_absolute_line(n)?;
                 pane.refresh()?;
             }
@@ -397,60 +1428,102 @@ impl App {
                 pane.refresh()?;
             }
             PeepEvent::SearchIncremental(s) => {
+                if self.typing_word.is_none() {
+                    // the search starts
+                    self.remember_jump(pane);
+                }
                 self.typing_word = Some(s.to_owned());
-                pane.set_message(Some(format!("/{}", s)));
-                if s.is_empty() {
-                    let _ = self.searcher.borrow_mut().set_pattern(s);
+                if !self.set_search_pattern(s) {
+                    // keep the highlight of the previous pattern until the pattern is fixed
+                } else if s.is_empty() {
+                    pane.set_current_match(None);
+                    self.counter.reset();
                     pane.show_highlight(false);
                 } else {
-                    let _ = self.searcher.borrow_mut().set_pattern(s);
+                    pane.set_current_match(None);
+                    self.counter.reset();
                     if let Some(pos) = self.search(pane.position()) {
-                        pane.goto_absolute_line(pos.1)?;
+                        pane.goto_match(pos.1, 0)?;
                     }
                     pane.show_highlight(true);
                 }
+                pane.set_message(self.search_message(pane));
                 pane.refresh()?;
             }
             PeepEvent::SearchTrigger => {
-                self.typing_word = None;
-                pane.set_message(self.mode_default_message());
+                if let Some(message) = self.pattern_error_message() {
+                    self.typing_word = None;
+                    pane.set_message(Some(message));
+                } else {
+                    self.save_history();
+                    self.typing_word = None;
+                    pane.set_message(self.search_message(pane));
+                }
                 pane.refresh()?;
             }
             PeepEvent::SearchNext => {
-                let cur_pos = pane.position();
-                let next_pos = (
-                    cur_pos.0,
-                    if cur_pos.1 == self.linebuf.borrow().len() as u16 - 1 {
-                        self.linebuf.borrow().len() as u16 - 1
-                    } else {
-                        cur_pos.1 + 1
-                    },
-                );
-
                 if self.searcher.borrow().as_str().is_empty() {
                     pane.show_highlight(false);
                 } else {
-                    if let Some(pos) = self.search(next_pos) {
-                        pane.goto_absolute_line(pos.1)?;
+                    if let Some((y, nth)) = self.next_match(pane) {
+                        self.remember_jump(pane);
+                        pane.goto_match(y, nth)?;
                     }
                     pane.show_highlight(true);
                 }
-                pane.set_message(self.mode_default_message());
+                pane.set_message(self.search_message(pane));
                 pane.refresh()?;
             }
             PeepEvent::SearchPrev => {
-                let cur_pos = pane.position();
-                let next_pos = (cur_pos.0, if cur_pos.1 == 0 { 0 } else { cur_pos.1 - 1 });
-
                 if self.searcher.borrow().as_str().is_empty() {
                     pane.show_highlight(false);
                 } else {
-                    if let Some(pos) = self.search_rev(next_pos) {
-                        pane.goto_absolute_line(pos.1)?;
+                    if let Some((y, nth)) = self.prev_match(pane) {
+                        self.remember_jump(pane);
+                        pane.goto_match(y, nth)?;
                     }
                     pane.show_highlight(true);
                 }
-                pane.set_message(self.mode_default_message());
+                pane.set_message(self.search_message(pane));
+                pane.refresh()?;
+            }
+            PeepEvent::ToggleSmartCase
+            | PeepEvent::ToggleIgnoreCase
+            | PeepEvent::ToggleFixedString => {
+                let message = self.toggle_search_mode(event);
+                pane.set_message(Some(message));
+                pane.refresh()?;
+            }
+            PeepEvent::FilterIncremental(s) => {
+                pane.set_message(Some(format!("&{}", s)));
+                pane.refresh()?;
+            }
+            PeepEvent::FilterTrigger(s) => {
+                self.apply_filter(s, pane)?;
+                pane.refresh()?;
+            }
+            PeepEvent::HighlightIncremental(s) => {
+                pane.set_message(Some(format!("{}{}", HIGHLIGHT_PROMPT, s)));
+                pane.refresh()?;
+            }
+            PeepEvent::HighlightTrigger(s) => {
+                self.apply_highlight(s, pane);
+                pane.refresh()?;
+            }
+            PeepEvent::CycleLogLevel => {
+                self.cycle_log_level(pane)?;
+                pane.refresh()?;
+            }
+            PeepEvent::ToggleHexView => {
+                self.toggle_hex_view(pane)?;
+                pane.refresh()?;
+            }
+            PeepEvent::GotoOffsetIncremental(s) => {
+                pane.set_message(Some(format!("{}{}", OFFSET_PROMPT, s)));
+                pane.refresh()?;
+            }
+            PeepEvent::GotoOffsetTrigger(s) => {
+                self.goto_offset(s, pane)?;
                 pane.refresh()?;
             }
             PeepEvent::Message(s) => {
@@ -459,19 +1532,50 @@ impl App {
             }
             PeepEvent::Cancel => {
                 self.typing_word = None;
+                self.pattern_error = None;
                 pane.set_message(self.mode_default_message());
                 pane.show_highlight(false);
+                pane.set_current_match(None);
+                pane.refresh()?;
+            }
+            &PeepEvent::SetMark(name) => {
+                self.set_mark(name, pane);
+                pane.refresh()?;
+            }
+            &PeepEvent::JumpToMark(name) => {
+                self.jump_to_mark(name, pane)?;
+                pane.refresh()?;
+            }
+            PeepEvent::NextFile => {
+                self.switch_document(self.cur_doc + 1, pane)?;
+                pane.refresh()?;
+            }
+            PeepEvent::PrevFile => {
+                self.switch_document(self.cur_doc.saturating_sub(1), pane)?;
                 pane.refresh()?;
             }
             PeepEvent::FollowMode => {
                 // Enter follow mode
                 self.follow_mode = true;
+                self.counter.reset();
                 // Reload file
-                self.read_buffer(DEFAULT_POLL_TIMEOUT_MS)?;
+                if self.is_multiplexed() {
+                    self.start_merged_view(pane)?;
+                } else {
+                    self.read_buffer(DEFAULT_POLL_TIMEOUT_MS)?;
+                }
                 pane.goto_bottom_of_lines()?;
                 pane.set_message(self.mode_default_message());
                 pane.refresh()?;
             }
+            PeepEvent::Resize => {
+                pane.fit_to_terminal()?;
+                pane.refresh()?;
+            }
+            &PeepEvent::MouseClick(_, row) if pane.is_message_bar_row(row) => {
+                // click on the message bar turns the page
+                self.handle_normal(&PeepEvent::MoveDownPages(1), pane)?;
+            }
             PeepEvent::Quit => {
                 pane.quit();
             }
@@ -506,29 +1610,42 @@ impl App {
             }
             PeepEvent::SearchIncremental(s) => {
                 self.typing_word = Some(s.to_owned());
-                pane.set_message(Some(format!("{}/{}", FOLLOWING_HL_MESSAGE, s)));
-                if s.is_empty() {
-                    let _ = self.searcher.borrow_mut().set_pattern(s);
-                    pane.show_highlight(false);
+                let mut message = format!("{}{}", FOLLOWING_HL_MESSAGE, self.search_prompt(s));
+                if !self.set_search_pattern(s) {
+                    // keep the highlight of the previous pattern until the pattern is fixed
+                    if let Some(ref e) = self.pattern_error {
+                        message = format!("{}  ({})", message, error_style(e));
+                    }
                 } else {
-                    let _ = self.searcher.borrow_mut().set_pattern(s);
-                    pane.show_highlight(true);
+                    pane.show_highlight(!s.is_empty());
                 }
+                pane.set_message(Some(message));
                 pane.refresh()?;
             }
             PeepEvent::SearchTrigger => {
+                match self.pattern_error_message() {
+                    Some(message) => pane.set_message(Some(message)),
+                    None => {
+                        self.save_history();
+                        pane.set_message(self.mode_default_message());
+                    }
+                }
                 self.typing_word = None;
-                pane.set_message(self.mode_default_message());
                 pane.refresh()?;
             }
             PeepEvent::Cancel => {
                 self.typing_word = None;
+                self.pattern_error = None;
                 pane.set_message(self.mode_default_message());
                 pane.show_highlight(false);
                 pane.refresh()?;
             }
-            PeepEvent::FileUpdated => {
-                self.read_buffer(DEFAULT_POLL_TIMEOUT_MS)?;
+            &PeepEvent::FileUpdated(i) => {
+                if self.is_multiplexed() {
+                    self.pull_document(i)?;
+                } else {
+                    self.read_buffer(DEFAULT_POLL_TIMEOUT_MS)?;
+                }
                 pane.goto_bottom_of_lines()?;
                 pane.set_message(self.mode_default_message());
                 pane.refresh()?;
@@ -536,9 +1653,58 @@ impl App {
             PeepEvent::FollowMode => {
                 // Leave follow mode
                 self.follow_mode = false;
+                self.counter.reset();
+                self.source_notice = None;
+                if self.is_multiplexed() {
+                    // back to the current file
+                    self.linebuf.borrow().sync();
+                    self.load_view(pane, self.linebuf.clone());
+                    pane.goto_bottom_of_lines()?;
+                }
                 pane.set_message(self.mode_default_message());
                 pane.refresh()?;
             }
+            PeepEvent::Resize => {
+                pane.fit_to_terminal()?;
+                pane.refresh()?;
+            }
+            PeepEvent::FilterIncremental(s) => {
+                pane.set_message(Some(format!("&{}", s)));
+                pane.refresh()?;
+            }
+            PeepEvent::FilterTrigger(s) => {
+                self.apply_filter(s, pane)?;
+                pane.refresh()?;
+            }
+            PeepEvent::HighlightIncremental(s) => {
+                pane.set_message(Some(format!("{}{}", HIGHLIGHT_PROMPT, s)));
+                pane.refresh()?;
+            }
+            PeepEvent::HighlightTrigger(s) => {
+                self.apply_highlight(s, pane);
+                pane.refresh()?;
+            }
+            PeepEvent::CycleLogLevel => {
+                self.cycle_log_level(pane)?;
+                pane.refresh()?;
+            }
+            PeepEvent::ToggleHexView => {
+                self.toggle_hex_view(pane)?;
+                pane.goto_bottom_of_lines()?;
+                pane.set_message(self.mode_default_message());
+                pane.refresh()?;
+            }
+            PeepEvent::ToggleSmartCase
+            | PeepEvent::ToggleIgnoreCase
+            | PeepEvent::ToggleFixedString => {
+                let message = self.toggle_search_mode(event);
+                pane.set_message(Some(message));
+                pane.refresh()?;
+            }
+            &PeepEvent::MouseClick(_, row) if pane.is_message_bar_row(row) => {
+                // click on the message bar leaves follow mode
+                self.handle_follow(&PeepEvent::FollowMode, pane)?;
+            }
             PeepEvent::Quit => {
                 pane.quit();
             }
@@ -552,25 +1718,178 @@ impl App {
         Ok(())
     }
 
-    fn search(&self, pos: (u16, u16)) -> Option<(u16, u16)> {
+    /// Return the text of the line as shown on the pane, which the pattern is matched with.
+    fn searchable_text(&self, line: &str) -> String {
+        ansi::expand_line(line, self.tab_width as usize, self.raw_control_chars).text
+    }
+
+    /// Return the number of matches in the line of the view.
+    fn count_matches(&self, y: usize) -> usize {
+        let view = self.view();
+        let view = view.borrow();
+        match view.line(y) {
+            Some(line) => self
+                .searcher
+                .borrow()
+                .find_iter_in(&self.searchable_text(&line), &*view, y)
+                .filter(|m| !m.is_continued())
+                .count(),
+            None => 0,
+        }
+    }
+
+    /// Return the match after the current match as the line and the index in the line.
+    /// If the current match is not on the pane, the search starts from the next of the top line.
+    fn next_match(&self, pane: &Pane) -> Option<(usize, usize)> {
+        let y = match pane
+            .current_match()
+            .filter(|&(y, _)| pane.is_line_visible(y))
+        {
+            Some((y, nth)) if nth + 1 < self.count_matches(y) => return Some((y, nth + 1)),
+            Some((y, _)) => y + 1,
+            None => cmp::min(
+                pane.position().1 + 1,
+                self.view().borrow().len().saturating_sub(1),
+            ),
+        };
+        self.search((0, y)).map(|(_, y)| (y, 0))
+    }
+
+    /// Return the match before the current match as the line and the index in the line.
+    /// If the current match is not on the pane, the search starts from the previous of the top line.
+    fn prev_match(&self, pane: &Pane) -> Option<(usize, usize)> {
+        let y = match pane
+            .current_match()
+            .filter(|&(y, _)| pane.is_line_visible(y))
+        {
+            Some((y, nth)) if nth > 0 => return Some((y, nth - 1)),
+            Some((y, _)) => y.checked_sub(1)?,
+            None => pane.position().1.saturating_sub(1),
+        };
+        let (_, y) = self.search_rev((0, y))?;
+        Some((y, self.count_matches(y).saturating_sub(1)))
+    }
+
+    fn search(&self, pos: (usize, usize)) -> Option<(usize, usize)> {
         let searcher = self.searcher.borrow();
-        let ref_linebuf = self.linebuf.borrow();
-        for (i, line) in ref_linebuf[(pos.1 as usize)..].iter().enumerate() {
-            if let Some(m) = searcher.find(line) {
-                return Some((m.start() as u16, pos.1 + i as u16));
+        let view = self.view();
+        let ref_linebuf = view.borrow();
+        let mut y = pos.1;
+        loop {
+            if y >= ref_linebuf.len() {
+                // wait for the rest of lines
+                ref_linebuf.sync();
+                if y >= ref_linebuf.len() {
+                    return None;
+                }
+            }
+            let lines = ref_linebuf.lines(y..y + SEARCH_CHUNK_LINES);
+            if lines.is_empty() {
+                // the lines can't be read, e.g. the file was truncated before the update
+                return None;
             }
+            for (i, line) in lines.iter().enumerate() {
+                let text = self.searchable_text(line);
+                if let Some(m) = searcher.find_in(&text, &*ref_linebuf, y + i) {
+                    return Some((m.start(), y + i));
+                }
+            }
+            y += lines.len();
         }
-        None
     }
 
-    fn search_rev(&self, pos: (u16, u16)) -> Option<(u16, u16)> {
+    fn search_rev(&self, pos: (usize, usize)) -> Option<(usize, usize)> {
         let searcher = self.searcher.borrow();
-        let ref_linebuf = self.linebuf.borrow();
-        for (i, line) in ref_linebuf[0..=(pos.1 as usize)].iter().rev().enumerate() {
-            if let Some(m) = searcher.find(line) {
-                return Some((m.start() as u16, pos.1 - i as u16));
+        let view = self.view();
+        let ref_linebuf = view.borrow();
+        let mut end = cmp::min(pos.1 + 1, ref_linebuf.len());
+        while end > 0 {
+            let start = end.saturating_sub(SEARCH_CHUNK_LINES);
+            let lines = ref_linebuf.lines(start..end);
+            for (i, line) in lines.iter().enumerate().rev() {
+                let text = self.searchable_text(line);
+                if let Some(m) = searcher.find_in(&text, &*ref_linebuf, start + i) {
+                    return Some((m.start(), start + i));
+                }
             }
+            end = start;
         }
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_swap_document() {
+        let first: Rc<RefCell<dyn LineSource>> = Rc::new(RefCell::new(vec!["a".to_owned(); 200]));
+        let second: Rc<RefCell<dyn LineSource>> = Rc::new(RefCell::new(vec!["b".to_owned()]));
+        let mut app = App::new();
+        app.documents = vec![Document::new("first"), Document::new("second")];
+        app.documents[1].linebuf = Some(second.clone());
+        app.file_path = "first".to_owned();
+        app.linebuf = first.clone();
+        app.searcher.borrow_mut().set_pattern("foo").unwrap();
+
+        // `:n` shows the second file from the head without the pattern
+        assert_eq!(app.swap_document(1, (3, 120)).unwrap(), (0, 0));
+        assert_eq!(app.file_path, "second");
+        assert!(Rc::ptr_eq(&app.linebuf, &second));
+        assert_eq!(app.searcher.borrow().as_str(), "");
+        app.searcher.borrow_mut().set_pattern("bar").unwrap();
+
+        // `:p` restores the first file
+        assert_eq!(app.swap_document(0, (0, 0)).unwrap(), (3, 120));
+        assert_eq!(app.file_path, "first");
+        assert!(Rc::ptr_eq(&app.linebuf, &first));
+        assert_eq!(app.searcher.borrow().as_str(), "foo");
+
+        // `:n` again restores the second file
+        assert_eq!(app.swap_document(1, (0, 150)).unwrap(), (0, 0));
+        assert!(Rc::ptr_eq(&app.linebuf, &second));
+        assert_eq!(app.searcher.borrow().as_str(), "bar");
+        assert_eq!(app.documents[0].state(), ((0, 150), "foo"));
+    }
+
+    #[test]
+    fn test_update_filter_replaced_source() {
+        let base = Rc::new(RefCell::new(vec![
+            "ERROR a".to_owned(),
+            "INFO b".to_owned(),
+        ]));
+        let mut filter = FilteredSource::new(base.clone(), 4, false);
+        filter.push_filter(Filter::parse("ERROR", SearchMode::default()).unwrap());
+        let filter = Rc::new(RefCell::new(filter));
+        let mut app = App::new();
+        app.filter = Some(filter.clone());
+
+        // the file was truncated, and has grown past the old length until the change is handled
+        *base.borrow_mut() = ["INFO c", "INFO d", "ERROR e"].map(String::from).to_vec();
+        app.update_filter(SourceChange::Truncated).unwrap();
+        assert_eq!(filter.borrow().lines(0..10), vec!["ERROR e"]);
+        assert_eq!(filter.borrow().origin_line(0), 2);
+
+        base.borrow_mut().push("ERROR f".to_owned());
+        app.update_filter(SourceChange::Appended).unwrap();
+        assert_eq!(filter.borrow().lines(0..10), vec!["ERROR e", "ERROR f"]);
+    }
+
+    #[test]
+    fn test_update_filter_recount() {
+        let base = Rc::new(RefCell::new(vec!["a b".to_owned(), "b".to_owned()]));
+        let mut app = App::new();
+        app.linebuf = base.clone();
+        app.shows_match_status = true;
+        app.searcher.borrow_mut().set_pattern("b").unwrap();
+        app.update_filter(SourceChange::Appended).unwrap();
+        assert_eq!(app.counter.total(), 2);
+
+        // the file was replaced, and has grown past the old length until the change is handled
+        *base.borrow_mut() = ["c", "c", "b c"].map(String::from).to_vec();
+        app.update_filter(SourceChange::Reopened).unwrap();
+        assert_eq!(app.counter.total(), 1);
+        assert_eq!(app.counter.index_of(2, 0), Some(0));
+    }
+}
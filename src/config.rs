@@ -0,0 +1,387 @@
+//! config module
+
+use crate::event::PeepEvent;
+use crate::keybind::{default::KeyBind, Key};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const KEYS_FILE_NAME: &str = "keys.toml";
+const OPTIONS_FILE_NAME: &str = "config.toml";
+const OPTIONS_ENV_NAME: &str = "PEEP_OPTIONS";
+
+/// Return the config directory of peep.
+/// $XDG_CONFIG_HOME/peep or ~/.config/peep
+pub fn config_dir() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))
+        .map(|d| d.join("peep"))
+}
+
+/// Load the key bindings file if it exists.
+/// Return the command table that the key bindings are applied to.
+pub fn load_keys() -> io::Result<Option<HashMap<Vec<Key>, PeepEvent>>> {
+    let path = match config_dir() {
+        Some(dir) => dir.join(KEYS_FILE_NAME),
+        None => return Ok(None),
+    };
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    parse_keys(&text)
+        .map(Some)
+        .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))
+}
+
+/// Return the path of the options file.
+pub fn options_file() -> Option<PathBuf> {
+    config_dir().map(|d| d.join(OPTIONS_FILE_NAME))
+}
+
+/// Read the options file and return the options as command line arguments.
+/// An empty list is returned if the file doesn't exist.
+pub fn read_options(path: &Path) -> io::Result<Vec<String>> {
+    match fs::read_to_string(path) {
+        Ok(text) => parse_options(&text)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e))),
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(vec![]),
+        Err(e) => Err(e),
+    }
+}
+
+/// Return the options in PEEP_OPTIONS environment variable.
+pub fn env_options() -> Vec<String> {
+    env::var(OPTIONS_ENV_NAME)
+        .map(|v| v.split_whitespace().map(|s| s.to_owned()).collect())
+        .unwrap_or_default()
+}
+
+/// Convert the options file into command line arguments.
+/// The keys are the long option names, and `false` turns the flag off with the `--no-` option.
+///
+/// ```toml
+/// lines = 5
+/// wrap = true
+/// ```
+fn parse_options(text: &str) -> io::Result<Vec<String>> {
+    let root = text
+        .parse::<toml::Value>()
+        .map_err(|e| invalid_data(e.to_string()))?;
+    let root = root
+        .as_table()
+        .ok_or_else(|| invalid_data("expected a table".to_owned()))?;
+
+    let mut args = vec![];
+    for (name, value) in root.iter() {
+        match value {
+            toml::Value::Boolean(true) => args.push(format!("--{}", name)),
+            toml::Value::Boolean(false) => args.push(format!("--no-{}", name)),
+            toml::Value::Integer(n) => args.push(format!("--{}={}", name, n)),
+            toml::Value::String(s) => args.push(format!("--{}={}", name, s)),
+            // the option given multiple times
+            toml::Value::Array(a) if a.iter().all(|v| v.is_str()) => {
+                for v in a {
+                    args.push(format!("--{}={}", name, v.as_str().unwrap()));
+                }
+            }
+            _ => return Err(invalid_data(format!("invalid value of '{}'", name))),
+        }
+    }
+    Ok(args)
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Parse the key bindings and return the command table.
+///
+/// ```toml
+/// # start from an empty table instead of the default key bindings
+/// use_defaults = false
+///
+/// [keys]
+/// "<C-v>" = "MoveDownPages"
+/// "gg" = "MoveToTopOfLines"
+/// "<Space>" = "None"   # unbind
+/// ```
+pub fn parse_keys(text: &str) -> io::Result<HashMap<Vec<Key>, PeepEvent>> {
+    let root = text
+        .parse::<toml::Value>()
+        .map_err(|e| invalid_data(e.to_string()))?;
+    let root = root
+        .as_table()
+        .ok_or_else(|| invalid_data("expected a table".to_owned()))?;
+
+    let mut cmap = match root.get("use_defaults") {
+        None => KeyBind::default_command_table(),
+        Some(toml::Value::Boolean(true)) => KeyBind::default_command_table(),
+        Some(toml::Value::Boolean(false)) => HashMap::new(),
+        Some(_) => return Err(invalid_data("use_defaults must be a boolean".to_owned())),
+    };
+    if let Some(k) = root.keys().find(|&k| k != "use_defaults" && k != "keys") {
+        return Err(invalid_data(format!("unknown field '{}'", k)));
+    }
+
+    let keys = match root.get("keys") {
+        None => return Ok(cmap),
+        Some(toml::Value::Table(keys)) => keys,
+        Some(_) => return Err(invalid_data("[keys] must be a table".to_owned())),
+    };
+    for (notation, action) in keys.iter() {
+        let key = parse_key_sequence(notation)?;
+        let action = action
+            .as_str()
+            .ok_or_else(|| invalid_data(format!("action of '{}' must be a string", notation)))?;
+        if action == "None" {
+            cmap.remove(&key);
+            continue;
+        }
+        let op = action_of(action).ok_or_else(|| {
+            invalid_data(format!("unknown action '{}' for '{}'", action, notation))
+        })?;
+        cmap.insert(key, op);
+    }
+
+    validate_prefixes(&cmap)?;
+    Ok(cmap)
+}
+
+/// Names of the special keys in the key notation
+const KEY_NAMES: [(&str, Key); 13] = [
+    ("Space", Key::Char(' ')),
+    ("Tab", Key::Char('\t')),
+    ("lt", Key::Char('<')),
+    ("Up", Key::Up),
+    ("Down", Key::Down),
+    ("Left", Key::Left),
+    ("Right", Key::Right),
+    ("PageUp", Key::PageUp),
+    ("PageDown", Key::PageDown),
+    ("Home", Key::Home),
+    ("End", Key::End),
+    ("WheelUp", Key::WheelUp),
+    ("WheelDown", Key::WheelDown),
+];
+
+/// Convert the key notation into the key sequence.
+/// `<C-x>` and the names of `KEY_NAMES` are available in addition to plain chars.
+fn parse_key_sequence(notation: &str) -> io::Result<Vec<Key>> {
+    let mut key = vec![];
+    let mut rest = notation;
+
+    while let Some(c) = rest.chars().next() {
+        if c == '<' {
+            let end = rest
+                .find('>')
+                .ok_or_else(|| invalid_data(format!("unclosed '<' in '{}'", notation)))?;
+            let name = &rest[1..end];
+            key.push(match KEY_NAMES.iter().find(|(n, _)| *n == name) {
+                Some(&(_, k)) => k,
+                None => match name.strip_prefix("C-") {
+                    Some(l) if l.len() == 1 && l.as_bytes()[0].is_ascii_alphabetic() => {
+                        Key::Char((l.as_bytes()[0].to_ascii_lowercase() & 0x1f) as char)
+                    }
+                    _ => {
+                        return Err(invalid_data(format!(
+                            "unknown key '<{}>' in '{}'",
+                            name, notation
+                        )))
+                    }
+                },
+            });
+            rest = &rest[end + 1..];
+        } else {
+            key.push(Key::Char(c));
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+
+    // these keys are handled before looking up the command table
+    let is_reserved = |k: &Key| matches!(k, Key::Char('\x1b' | '\x08' | '\x7f'));
+    match key.first() {
+        None => Err(invalid_data("empty key".to_owned())),
+        Some(Key::Char(c)) if *c == '/' || *c == '&' || ('1'..='9').contains(c) => {
+            Err(invalid_data(format!(
+                "'{}' is reserved for search, filter and count",
+                notation
+            )))
+        }
+        _ if key.iter().any(is_reserved) => Err(invalid_data(format!(
+            "'{}' contains a reserved key",
+            notation
+        ))),
+        _ => Ok(key),
+    }
+}
+
+/// Convert the key sequence into the key notation.
+fn key_notation(key: &[Key]) -> String {
+    key.iter()
+        .map(|k| match KEY_NAMES.iter().find(|(_, v)| v == k) {
+            Some((n, _)) => format!("<{}>", n),
+            None => match *k {
+                Key::Char(c) if c.is_ascii_control() => {
+                    format!("<C-{}>", ((c as u8) | 0x60) as char)
+                }
+                Key::Char(c) => c.to_string(),
+                _ => format!("{:?}", k),
+            },
+        })
+        .collect()
+}
+
+/// Check that every key sequence is reachable.
+/// A sequence is never completed if its prefix is bound to another action.
+fn validate_prefixes(cmap: &HashMap<Vec<Key>, PeepEvent>) -> io::Result<()> {
+    let mut keys: Vec<&Vec<Key>> = cmap.keys().collect();
+    keys.sort();
+    for w in keys.windows(2) {
+        if w[1].starts_with(w[0]) {
+            return Err(invalid_data(format!(
+                "'{}' is unreachable because '{}' is bound",
+                key_notation(w[1]),
+                key_notation(w[0])
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Return the event of the action name.
+/// A count prefix is applied to the event by the key bind.
+fn action_of(name: &str) -> Option<PeepEvent> {
+    Some(match name {
+        "MoveDown" => PeepEvent::MoveDown(1),
+        "MoveUp" => PeepEvent::MoveUp(1),
+        "MoveLeft" => PeepEvent::MoveLeft(1),
+        "MoveRight" => PeepEvent::MoveRight(1),
+        "MoveDownHalfPages" => PeepEvent::MoveDownHalfPages(1),
+        "MoveUpHalfPages" => PeepEvent::MoveUpHalfPages(1),
+        "MoveLeftHalfPages" => PeepEvent::MoveLeftHalfPages(1),
+        "MoveRightHalfPages" => PeepEvent::MoveRightHalfPages(1),
+        "MoveDownPages" => PeepEvent::MoveDownPages(1),
+        "MoveUpPages" => PeepEvent::MoveUpPages(1),
+        "MoveToHeadOfLine" => PeepEvent::MoveToHeadOfLine,
+        "MoveToEndOfLine" => PeepEvent::MoveToEndOfLine,
+        "MoveToTopOfLines" => PeepEvent::MoveToTopOfLines,
+        "MoveToBottomOfLines" => PeepEvent::MoveToBottomOfLines,
+        "ToggleLineNumberPrinting" => PeepEvent::ToggleLineNumberPrinting,
+        "ToggleLineWraps" => PeepEvent::ToggleLineWraps,
+        "IncrementLines" => PeepEvent::IncrementLines(1),
+        "DecrementLines" => PeepEvent::DecrementLines(1),
+        "SetNumOfLines" => PeepEvent::SetNumOfLines(0),
+        "SearchNext" => PeepEvent::SearchNext,
+        "SearchPrev" => PeepEvent::SearchPrev,
+        "ToggleSmartCase" => PeepEvent::ToggleSmartCase,
+        "ToggleIgnoreCase" => PeepEvent::ToggleIgnoreCase,
+        "ToggleFixedString" => PeepEvent::ToggleFixedString,
+        "Highlight" => PeepEvent::HighlightIncremental(String::new()),
+        "CycleLogLevel" => PeepEvent::CycleLogLevel,
+        "ToggleHexView" => PeepEvent::ToggleHexView,
+        "GotoOffset" => PeepEvent::GotoOffsetIncremental(String::new()),
+        "SetMark" => PeepEvent::SetMark('\0'),
+        "JumpToMark" => PeepEvent::JumpToMark('\0'),
+        "NextFile" => PeepEvent::NextFile,
+        "PrevFile" => PeepEvent::PrevFile,
+        "Quit" => PeepEvent::Quit,
+        "QuitWithClear" => PeepEvent::QuitWithClear,
+        "FollowMode" => PeepEvent::FollowMode,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_options() {
+        assert_eq!(
+            parse_options("lines = 5\nwrap = true\nfollow = false\ntab-width = \"8\"").unwrap(),
+            vec!["--no-follow", "--lines=5", "--tab-width=8", "--wrap"]
+        );
+        assert_eq!(
+            parse_options("hl = [\"ERROR=red\", \"WARN\"]").unwrap(),
+            vec!["--hl=ERROR=red", "--hl=WARN"]
+        );
+        assert!(parse_options("lines = [1]").is_err());
+        assert!(parse_options("lines = ").is_err());
+    }
+
+    fn keys(s: &str) -> Vec<Key> {
+        s.chars().map(Key::Char).collect()
+    }
+
+    #[test]
+    fn test_parse_key_sequence() {
+        assert_eq!(parse_key_sequence("j").unwrap(), keys("j"));
+        assert_eq!(parse_key_sequence("gg").unwrap(), keys("gg"));
+        assert_eq!(parse_key_sequence("<C-v>").unwrap(), keys("\x16"));
+        assert_eq!(parse_key_sequence("<C-X>k").unwrap(), keys("\x18k"));
+        assert_eq!(parse_key_sequence("<Space><lt>").unwrap(), keys(" <"));
+        assert_eq!(parse_key_sequence("あ").unwrap(), keys("あ"));
+        assert_eq!(
+            parse_key_sequence("<PageDown><Up>").unwrap(),
+            vec![Key::PageDown, Key::Up]
+        );
+
+        assert!(parse_key_sequence("").is_err());
+        assert!(parse_key_sequence("/").is_err());
+        assert!(parse_key_sequence("5j").is_err());
+        assert!(parse_key_sequence("<C-[>").is_err());
+        assert!(parse_key_sequence("<Foo>").is_err());
+        assert!(parse_key_sequence("<C-v").is_err());
+
+        assert_eq!(
+            key_notation(&parse_key_sequence("<C-v>a<Space><Home>").unwrap()),
+            "<C-v>a<Space><Home>"
+        );
+    }
+
+    #[test]
+    fn test_parse_keys() {
+        let cmap = parse_keys(
+            r#"
+            [keys]
+            "<C-v>" = "MoveDownPages"
+            "J" = "MoveDown"
+            "<Space>" = "None"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(cmap.get(&keys("\x16")), Some(&PeepEvent::MoveDownPages(1)));
+        assert_eq!(cmap.get(&keys("J")), Some(&PeepEvent::MoveDown(1)));
+        assert_eq!(cmap.get(&keys("j")), Some(&PeepEvent::MoveDown(1)));
+        assert_eq!(cmap.get(&keys(" ")), None);
+        assert_eq!(
+            cmap.get(&vec![Key::End]),
+            Some(&PeepEvent::MoveToBottomOfLines)
+        );
+
+        let cmap = parse_keys(
+            r#"
+            use_defaults = false
+            [keys]
+            "gg" = "MoveToTopOfLines"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(cmap.len(), 1);
+        assert_eq!(cmap.get(&keys("gg")), Some(&PeepEvent::MoveToTopOfLines));
+
+        // the default 'g' shadows 'gg'
+        assert!(parse_keys("[keys]\n\"gg\" = \"MoveToTopOfLines\"").is_err());
+        assert!(parse_keys("[keys]\n\"x\" = \"Jump\"").is_err());
+        assert!(parse_keys("[keys]\n\"x\" = 1").is_err());
+        assert!(parse_keys("use_defaults = 1").is_err());
+        assert!(parse_keys("key = 1").is_err());
+        assert!(parse_keys("[keys").is_err());
+    }
+}
@@ -53,6 +53,25 @@ pub mod cursor_ext {
             write!(f, csi!("{}G"), self.0)
         }
     }
+
+    derive_csi_sequence!("Request the cursor position report", RequestPosition, "6n");
+}
+
+pub mod mouse {
+    //! Mouse reporting
+
+    use std::fmt;
+
+    derive_csi_sequence!(
+        "Enable the mouse reporting in SGR format",
+        EnableReporting,
+        "?1000h\x1b[?1006h"
+    );
+    derive_csi_sequence!(
+        "Disable the mouse reporting in SGR format",
+        DisableReporting,
+        "?1006l\x1b[?1000l"
+    );
 }
 
 pub mod cursor_style {
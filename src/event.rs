@@ -1,20 +1,20 @@
 #[derive(Clone, Debug, PartialEq)]
 pub enum PeepEvent {
-    MoveDown(u16),
-    MoveUp(u16),
-    MoveLeft(u16),
-    MoveRight(u16),
-    MoveDownHalfPages(u16),
-    MoveUpHalfPages(u16),
-    MoveLeftHalfPages(u16),
-    MoveRightHalfPages(u16),
-    MoveDownPages(u16),
-    MoveUpPages(u16),
+    MoveDown(usize),
+    MoveUp(usize),
+    MoveLeft(usize),
+    MoveRight(usize),
+    MoveDownHalfPages(usize),
+    MoveUpHalfPages(usize),
+    MoveLeftHalfPages(usize),
+    MoveRightHalfPages(usize),
+    MoveDownPages(usize),
+    MoveUpPages(usize),
     MoveToHeadOfLine,
     MoveToEndOfLine,
     MoveToTopOfLines,
     MoveToBottomOfLines,
-    MoveToLineNumber(u16),
+    MoveToLineNumber(usize),
 
     ToggleLineNumberPrinting,
     ToggleLineWraps,
@@ -26,14 +26,33 @@ pub enum PeepEvent {
     SearchTrigger,
     SearchNext,
     SearchPrev,
+    FilterIncremental(String),
+    FilterTrigger(String),
+    HighlightIncremental(String),
+    HighlightTrigger(String),
+    CycleLogLevel,
+    ToggleHexView,
+    GotoOffsetIncremental(String),
+    GotoOffsetTrigger(String),
+    ToggleSmartCase,
+    ToggleIgnoreCase,
+    ToggleFixedString,
+    SetMark(char),
+    JumpToMark(char),
 
     Message(Option<String>),
 
+    NextFile,
+    PrevFile,
+
     Cancel,
     Quit,
     QuitWithClear,
 
+    MouseClick(u16, u16),
+    Resize,
+
     FollowMode,
-    FileUpdated,
+    FileUpdated(usize),
     SigInt,
 }
@@ -1,11 +1,16 @@
 use super::*;
 use mio;
+use std::fs;
 use std::io;
+use std::os::unix::fs::MetadataExt;
 use std::os::unix::io::{AsRawFd, RawFd};
 use std::time::Duration;
 
 pub struct FileWatcher {
+    file_path: String,
     inotify: inotify::Inotify,
+    wd: Option<inotify::WatchDescriptor>,
+    ino: u64,
     poll: mio::Poll,
     events: mio::Events,
     buffer: [u8; 1024],
@@ -14,7 +19,8 @@ pub struct FileWatcher {
 impl FileWatcher {
     pub fn new(file_path: &str) -> io::Result<Self> {
         let mut inotify = inotify::Inotify::init()?;
-        inotify.add_watch(file_path, inotify::WatchMask::MODIFY)?;
+        let wd = inotify.add_watch(file_path, Self::watch_mask())?;
+        let ino = fs::metadata(file_path)?.ino();
         let poll = mio::Poll::new()?;
         let events = mio::Events::with_capacity(1024);
 
@@ -25,23 +31,81 @@ impl FileWatcher {
         )?;
 
         Ok(Self {
+            file_path: file_path.to_owned(),
             inotify,
+            wd: Some(wd),
+            ino,
             poll,
             events,
             buffer: [0u8; 1024],
         })
     }
+
+    /// ATTRIB is needed to notice unlink of the file that is still opened.
+    fn watch_mask() -> inotify::WatchMask {
+        inotify::WatchMask::MODIFY
+            | inotify::WatchMask::ATTRIB
+            | inotify::WatchMask::MOVE_SELF
+            | inotify::WatchMask::DELETE_SELF
+    }
+
+    /// Watch the file path again after it was renamed or removed.
+    /// Return whether the watch is available or not.
+    fn rewatch(&mut self) -> bool {
+        if let Some(wd) = self.wd.take() {
+            // the watch may be already removed by kernel
+            let _ = self.inotify.rm_watch(wd);
+        }
+        self.wd = self
+            .inotify
+            .add_watch(&self.file_path, Self::watch_mask())
+            .ok();
+        if let Ok(m) = fs::metadata(&self.file_path) {
+            self.ino = m.ino();
+        }
+        self.wd.is_some()
+    }
+
+    /// Return whether the file path still points the watching file.
+    fn is_path_alive(&self) -> bool {
+        fs::metadata(&self.file_path).is_ok_and(|m| m.ino() == self.ino)
+    }
 }
 
 impl FileWatch for FileWatcher {
     fn watch(&mut self, timeout: Option<Duration>) -> io::Result<Option<bool>> {
+        if self.wd.is_none() {
+            // the file has gone. retry until the new file appears.
+            let timeout = timeout.map(|t| cmp::min(t, Duration::from_millis(REWATCH_INTERVAL_MS)));
+            self.poll.poll(&mut self.events, timeout)?;
+            return Ok(if self.rewatch() { Some(false) } else { None });
+        }
+
         self.poll.poll(&mut self.events, timeout)?;
         Ok(if self.events.is_empty() {
             None
         } else {
-            let evt = &self.events.iter().next();
-            self.inotify.read_events(&mut self.buffer)?;
-            evt.as_ref().map(|e| e.is_readable())
+            let is_readable = self.events.iter().next().map(|e| e.is_readable());
+            // drop events of the old watch that was removed by rewatch()
+            let wd = &self.wd;
+            let mask = self
+                .inotify
+                .read_events(&mut self.buffer)?
+                .filter(|e| Some(&e.wd) == wd.as_ref())
+                .fold(inotify::EventMask::empty(), |acc, e| acc | e.mask);
+            let is_replaced = mask.intersects(
+                inotify::EventMask::MOVE_SELF
+                    | inotify::EventMask::DELETE_SELF
+                    | inotify::EventMask::IGNORED,
+            ) || (mask.contains(inotify::EventMask::ATTRIB)
+                && !self.is_path_alive());
+            if is_replaced {
+                self.rewatch();
+            } else if !mask.contains(inotify::EventMask::MODIFY) {
+                // ignore the attribute change by reading the file
+                return Ok(None);
+            }
+            is_readable
         })
     }
 }
@@ -1,11 +1,13 @@
 use super::*;
 use mio;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::{self, Seek, SeekFrom};
+use std::os::unix::fs::MetadataExt;
 use std::os::unix::io::{AsRawFd, RawFd};
 use std::time::Duration;
 
 pub struct FileWatcher {
+    file_path: String,
     file: File,
     poll: mio::Poll,
     events: mio::Events,
@@ -24,13 +26,46 @@ impl FileWatcher {
             mio::Interest::READABLE,
         )?;
 
-        Ok(Self { file, poll, events })
+        Ok(Self {
+            file_path: file_path.to_owned(),
+            file,
+            poll,
+            events,
+        })
+    }
+
+    /// Return whether the file path points another file than the watching one.
+    fn is_replaced(&self) -> bool {
+        match (fs::metadata(&self.file_path), self.file.metadata()) {
+            (Ok(new), Ok(cur)) => new.dev() != cur.dev() || new.ino() != cur.ino(),
+            _ => false,
+        }
+    }
+
+    /// Watch the file path again after it was renamed or removed.
+    fn rewatch(&mut self) -> io::Result<()> {
+        let mut file = File::open(&self.file_path)?;
+        file.seek(SeekFrom::End(0))?;
+        self.poll
+            .registry()
+            .deregister(&mut mio::unix::SourceFd(&self.file.as_raw_fd()))?;
+        self.poll.registry().register(
+            &mut mio::unix::SourceFd(&file.as_raw_fd()),
+            mio::Token(0),
+            mio::Interest::READABLE,
+        )?;
+        self.file = file;
+        Ok(())
     }
 }
 
 impl FileWatch for FileWatcher {
     fn watch(&mut self, timeout: Option<Duration>) -> io::Result<Option<bool>> {
+        let timeout = timeout.map(|t| cmp::min(t, Duration::from_millis(REWATCH_INTERVAL_MS)));
         self.poll.poll(&mut self.events, timeout)?;
+        if self.is_replaced() && self.rewatch().is_ok() {
+            return Ok(Some(false));
+        }
         self.file.seek(SeekFrom::End(0))?;
         Ok(if self.events.is_empty() {
             None
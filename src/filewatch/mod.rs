@@ -1,4 +1,5 @@
 use crate::event::PeepEvent;
+use std::cmp;
 use std::io;
 use std::os::unix::io::AsRawFd;
 use std::sync::mpsc;
@@ -25,6 +26,8 @@ pub trait FileWatch {
 }
 
 const NONE_WAIT_SEC: u64 = 60;
+/// Interval to look for the new file after the watching file was renamed or removed.
+const REWATCH_INTERVAL_MS: u64 = 1000;
 
 pub struct Timeout;
 
@@ -36,7 +39,8 @@ impl FileWatch for Timeout {
     }
 }
 
-pub fn file_watcher(file_path: &str, event_sender: &mpsc::Sender<PeepEvent>) {
+/// Watch the file and send FileUpdated event with the source index.
+pub fn file_watcher(file_path: &str, source: usize, event_sender: &mpsc::Sender<PeepEvent>) {
     let mut fw: FileWatcher;
     let mut tm = Timeout;
     let mut sw: StdinWatcher;
@@ -59,7 +63,7 @@ pub fn file_watcher(file_path: &str, event_sender: &mpsc::Sender<PeepEvent>) {
 
     loop {
         if filewatcher.watch(Some(default_timeout)).unwrap().is_some() {
-            event_sender.send(PeepEvent::FileUpdated).unwrap();
+            event_sender.send(PeepEvent::FileUpdated(source)).unwrap();
         }
     }
 }
@@ -0,0 +1,297 @@
+//! filter module
+
+use crate::ansi;
+use crate::linesource::{LineSource, SourceChange};
+use crate::log::{Level, LogParser};
+use crate::search::{ModalSearcher, Search, SearchMode};
+use std::cell::RefCell;
+use std::cmp;
+use std::io;
+use std::ops;
+use std::rc::Rc;
+
+/// Number of lines that are read from the base source at once.
+const SCAN_CHUNK_LINES: usize = 1024;
+
+/// Pattern that lines must match, or must not match if it is inverse.
+pub struct Filter {
+    searcher: ModalSearcher,
+    inverse: bool,
+}
+
+impl Filter {
+    /// Parse the filter command text. The leading `!` makes the filter inverse.
+    pub fn parse(text: &str, mode: SearchMode) -> io::Result<Self> {
+        let (pat, inverse) = match text.strip_prefix('!') {
+            Some(pat) => (pat, true),
+            None => (text, false),
+        };
+        let mut searcher = ModalSearcher::with_mode(mode);
+        searcher.set_pattern(pat)?;
+        Ok(Self { searcher, inverse })
+    }
+
+    fn is_match(&self, text: &str) -> bool {
+        self.searcher.find(text).is_some() != self.inverse
+    }
+
+    /// Return the filter command text.
+    pub fn as_str(&self) -> String {
+        format!(
+            "{}{}",
+            if self.inverse { "!" } else { "" },
+            self.searcher.as_str()
+        )
+    }
+}
+
+/// Line source that shows only the lines of the base source that pass all filters.
+/// The lines are mapped to the line numbers of the base source.
+pub struct FilteredSource {
+    base: Rc<RefCell<dyn LineSource>>,
+    filters: Vec<Filter>,
+    // minimum log level and the parser to find the level of lines
+    min_level: Option<(Level, LogParser)>,
+    // how the lines are shown, which the filters are matched with
+    tab_width: usize,
+    raw_control_chars: bool,
+    // indexes of the base lines that pass the filters
+    origins: Vec<usize>,
+    // number of base lines already scanned
+    scanned: usize,
+}
+
+impl FilteredSource {
+    /// The filters are matched with the lines as shown on the pane with the tab width
+    /// and the raw control chars mode.
+    pub fn new(
+        base: Rc<RefCell<dyn LineSource>>,
+        tab_width: usize,
+        raw_control_chars: bool,
+    ) -> Self {
+        Self {
+            base,
+            filters: Vec::new(),
+            min_level: None,
+            tab_width,
+            raw_control_chars,
+            origins: Vec::new(),
+            scanned: 0,
+        }
+    }
+
+    /// Stack the filter on the current filters.
+    pub fn push_filter(&mut self, filter: Filter) {
+        let base = self.base.clone();
+        let base = base.borrow();
+        let (tab_width, raw_control_chars) = (self.tab_width, self.raw_control_chars);
+        self.origins.retain(|&n| {
+            base.line(n).is_some_and(|line| {
+                filter.is_match(&ansi::expand_line(&line, tab_width, raw_control_chars).text)
+            })
+        });
+        self.filters.push(filter);
+    }
+
+    /// Return the index of the first line at or after the line of the base source.
+    pub fn position_of(&self, origin: usize) -> usize {
+        self.origins.partition_point(|&n| n < origin)
+    }
+
+    /// Return the stacked filters.
+    pub fn filters(&self) -> &[Filter] {
+        &self.filters
+    }
+
+    /// Show only the lines whose log level is the level or higher.
+    /// Lines without a level are always shown. None shows all levels.
+    pub fn set_min_level(&mut self, level: Option<Level>) {
+        if level == self.min_level() {
+            return;
+        }
+        self.min_level = level.map(|level| (level, LogParser::new()));
+        self.rescan();
+    }
+
+    pub fn min_level(&self) -> Option<Level> {
+        self.min_level.as_ref().map(|(level, _)| *level)
+    }
+
+    /// Return whether the text passes the filters and the minimum level.
+    fn is_match(&self, text: &str) -> bool {
+        self.filters.iter().all(|f| f.is_match(text))
+            && self
+                .min_level
+                .as_ref()
+                .is_none_or(|(min, parser)| parser.level_of(text).is_none_or(|l| l >= *min))
+    }
+
+    /// Replace the base source, and filter it from the head.
+    pub fn set_base(&mut self, base: Rc<RefCell<dyn LineSource>>) {
+        self.base = base;
+        self.rescan();
+    }
+
+    /// Filter the base source from the head.
+    pub fn rescan(&mut self) {
+        self.origins.clear();
+        self.scanned = 0;
+        self.scan();
+    }
+
+    /// Filter the base lines that are not scanned yet.
+    fn scan(&mut self) {
+        let base = self.base.clone();
+        let base = base.borrow();
+        base.sync();
+
+        // the last line may be extended after it was scanned
+        if self.scanned > 0 {
+            self.scanned -= 1;
+            if self.origins.last() == Some(&self.scanned) {
+                self.origins.pop();
+            }
+        }
+
+        while self.scanned < base.len() {
+            let lines = base.lines(self.scanned..self.scanned + SCAN_CHUNK_LINES);
+            if lines.is_empty() {
+                break;
+            }
+            for (i, line) in lines.iter().enumerate() {
+                let text = ansi::expand_line(line, self.tab_width, self.raw_control_chars).text;
+                if self.is_match(&text) {
+                    self.origins.push(self.scanned + i);
+                }
+            }
+            self.scanned += lines.len();
+        }
+    }
+}
+
+impl LineSource for FilteredSource {
+    fn len(&self) -> usize {
+        self.origins.len()
+    }
+
+    fn lines(&self, range: ops::Range<usize>) -> Vec<String> {
+        let end = cmp::min(range.end, self.origins.len());
+        let start = cmp::min(range.start, end);
+        let base = self.base.borrow();
+        self.origins[start..end]
+            .iter()
+            .filter_map(|&n| base.line(n))
+            .collect()
+    }
+
+    /// Pick up the lines appended to the base source.
+    /// The base source must be updated in advance, and the filter must be rescanned
+    /// instead if the base source was truncated or replaced.
+    fn update(&mut self) -> io::Result<SourceChange> {
+        if self.base.borrow().len() < self.scanned {
+            self.rescan();
+            return Ok(SourceChange::Truncated);
+        }
+        self.scan();
+        Ok(SourceChange::Appended)
+    }
+
+    fn sync(&self) {
+        self.base.borrow().sync();
+    }
+
+    fn tag(&self, n: usize) -> Option<(usize, String)> {
+        self.origins
+            .get(n)
+            .and_then(|&origin| self.base.borrow().tag(origin))
+    }
+
+    fn tag_width(&self) -> usize {
+        self.base.borrow().tag_width()
+    }
+
+    fn origin_line(&self, n: usize) -> usize {
+        match self.origins.get(n) {
+            Some(&origin) => self.base.borrow().origin_line(origin),
+            None => n,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gen_source(lines: &[&str]) -> Rc<RefCell<Vec<String>>> {
+        Rc::new(RefCell::new(lines.iter().map(|l| l.to_string()).collect()))
+    }
+
+    #[test]
+    fn test_filter() {
+        let base = gen_source(&["INFO a", "ERROR b", "DEBUG c", "ERROR d debug"]);
+        let mut view = FilteredSource::new(base.clone(), 4, false);
+        view.rescan();
+        assert_eq!(view.len(), 4);
+
+        view.push_filter(Filter::parse("ERROR", SearchMode::default()).unwrap());
+        assert_eq!(view.lines(0..10), vec!["ERROR b", "ERROR d debug"]);
+        assert_eq!(view.origin_line(1), 3);
+        assert_eq!(view.position_of(2), 1);
+        assert_eq!(view.position_of(3), 1);
+
+        // stacked inverse filter
+        view.push_filter(Filter::parse("!debug", SearchMode::default()).unwrap());
+        assert_eq!(view.lines(0..10), vec!["ERROR b"]);
+        assert_eq!(view.origin_line(0), 1);
+        assert_eq!(
+            view.filters()
+                .iter()
+                .map(|f| f.as_str())
+                .collect::<Vec<_>>(),
+            vec!["ERROR", "!debug"]
+        );
+
+        // appended lines
+        base.borrow_mut().push("ERROR e".to_owned());
+        base.borrow_mut().push("INFO f".to_owned());
+        assert_eq!(view.update().unwrap(), SourceChange::Appended);
+        assert_eq!(view.lines(0..10), vec!["ERROR b", "ERROR e"]);
+        assert_eq!(view.origin_line(1), 4);
+
+        // truncated
+        base.borrow_mut().truncate(1);
+        assert_eq!(view.update().unwrap(), SourceChange::Truncated);
+        assert!(view.is_empty());
+
+        assert!(Filter::parse("(", SearchMode::default()).is_err());
+    }
+
+    #[test]
+    fn test_min_level() {
+        let base = gen_source(&["INFO a", "plain", "ERROR b", "DEBUG c", "WARN d"]);
+        let mut view = FilteredSource::new(base, 4, false);
+        view.set_min_level(Some(Level::Warn));
+        assert_eq!(view.lines(0..10), vec!["plain", "ERROR b", "WARN d"]);
+
+        view.push_filter(Filter::parse("!WARN", SearchMode::default()).unwrap());
+        view.set_min_level(Some(Level::Info));
+        assert_eq!(view.lines(0..10), vec!["INFO a", "plain", "ERROR b"]);
+        assert_eq!(view.min_level(), Some(Level::Info));
+
+        view.set_min_level(None);
+        assert_eq!(view.len(), 4);
+    }
+
+    #[test]
+    fn test_filter_extended_last_line() {
+        let base = gen_source(&["a", "b"]);
+        let mut view = FilteredSource::new(base.clone(), 4, true);
+        view.push_filter(Filter::parse("bc", SearchMode::default()).unwrap());
+        view.rescan();
+        assert!(view.is_empty());
+
+        base.borrow_mut().last_mut().unwrap().push_str("\x1b[31mc");
+        view.update().unwrap();
+        assert_eq!(view.lines(0..10), vec!["b\x1b[31mc"]);
+    }
+}
@@ -0,0 +1,262 @@
+//! hexdump module
+
+use crate::linesource::{LineSource, SourceChange};
+use crate::printable;
+use std::cell::RefCell;
+use std::cmp;
+use std::fmt::Write;
+use std::io;
+use std::ops;
+use std::rc::Rc;
+
+/// Number of bytes shown in a line of the hex view
+pub const BYTES_PER_LINE: usize = 16;
+/// Number of bytes whose hex digits are grouped without spaces
+const GROUP_BYTES: usize = 2;
+/// Width of the hex digits of a full line including the spaces between the groups
+const HEX_WIDTH: usize = BYTES_PER_LINE * 2 + BYTES_PER_LINE / GROUP_BYTES - 1;
+
+/// Format the bytes like a line of `xxd` without the offset:
+/// the hex digits in groups of 2 bytes and the ASCII chars, where unprintable bytes are `.`.
+///
+/// `4865 6c6c 6f0a                           Hello.`
+pub fn format_line(bytes: &[u8]) -> String {
+    let mut line = String::with_capacity(HEX_WIDTH + 2 + BYTES_PER_LINE);
+    for (i, b) in bytes.iter().enumerate() {
+        if i > 0 && i % GROUP_BYTES == 0 {
+            line.push(' ');
+        }
+        let _ = write!(line, "{:02x}", b);
+    }
+    // the ASCII column is aligned even if the line is short
+    let pad = HEX_WIDTH.saturating_sub(line.len()) + 2;
+    line.push_str(&" ".repeat(pad));
+    line.extend(bytes.iter().map(|&b| match b {
+        0x20..=0x7e => b as char,
+        _ => '.',
+    }));
+    line
+}
+
+/// Return the column of the hex digits of the byte at the index in the line.
+pub fn column_of(i: usize) -> usize {
+    i * 2 + i / GROUP_BYTES
+}
+
+/// Parse the offset of `0x` prefixed hex digits or decimal digits.
+pub fn parse_offset(text: &str) -> io::Result<u64> {
+    let text = text.trim();
+    let parsed = match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => text.parse(),
+    };
+    parsed.map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid offset"))
+}
+
+/// Line source that shows the bytes of the base source as a hex dump like `xxd`.
+/// The line n shows the 16 bytes from the offset n * 16.
+///
+/// The raw bytes are read from the base source if it keeps them, like files.
+/// Otherwise the lines of the base source are encoded with LF line ends, like standard input.
+pub struct HexSource {
+    base: Rc<RefCell<dyn LineSource>>,
+    // bytes encoded from the lines if the base source doesn't keep the raw bytes
+    encoded: Option<Vec<u8>>,
+    // offsets of the heads of the encoded lines
+    heads: Vec<u64>,
+}
+
+impl HexSource {
+    pub fn new(base: Rc<RefCell<dyn LineSource>>) -> Self {
+        let mut src = Self {
+            base,
+            encoded: None,
+            heads: Vec::new(),
+        };
+        src.encode();
+        src
+    }
+
+    /// Replace the base source, and dump it from the head.
+    pub fn set_base(&mut self, base: Rc<RefCell<dyn LineSource>>) {
+        self.base = base;
+        self.encoded = None;
+        self.encode();
+    }
+
+    /// Encode the lines appended to the base source if it doesn't keep the raw bytes.
+    fn encode(&mut self) {
+        let base = self.base.clone();
+        let base = base.borrow();
+        if base.byte_len().is_some() {
+            self.encoded = None;
+            return;
+        }
+        base.sync();
+        let len = base.len();
+        let encoded = match self.encoded {
+            Some(ref mut encoded) if self.heads.len() <= len => {
+                // the last line may be extended after it was encoded
+                if let Some(head) = self.heads.pop() {
+                    encoded.truncate(head as usize);
+                }
+                encoded
+            }
+            _ => {
+                self.heads.clear();
+                self.encoded.insert(Vec::new())
+            }
+        };
+        for line in base.lines(self.heads.len()..len) {
+            self.heads.push(encoded.len() as u64);
+            encoded.extend(printable::encode_lossy(&line));
+            encoded.push(b'\n');
+        }
+    }
+
+    fn total_bytes(&self) -> u64 {
+        match self.encoded {
+            Some(ref encoded) => encoded.len() as u64,
+            None => self.base.borrow().byte_len().unwrap_or(0),
+        }
+    }
+
+    /// Return the offset of the head of the line of the base source.
+    pub fn offset_of_base_line(&self, n: usize) -> u64 {
+        match self.encoded {
+            Some(_) => self.heads.get(n).cloned(),
+            None => self.base.borrow().offset_of_line(n),
+        }
+        .unwrap_or_else(|| self.total_bytes())
+    }
+
+    /// Return the line of the base source that has the byte at the offset.
+    pub fn base_line_at_offset(&self, offset: u64) -> usize {
+        match self.encoded {
+            Some(_) => self
+                .heads
+                .partition_point(|&h| h <= offset)
+                .saturating_sub(1),
+            None => self.base.borrow().line_at_offset(offset).unwrap_or(0),
+        }
+    }
+
+    fn read(&self, range: ops::Range<u64>) -> Vec<u8> {
+        match self.encoded {
+            Some(ref encoded) => {
+                let end = cmp::min(range.end as usize, encoded.len());
+                let start = cmp::min(range.start as usize, end);
+                encoded[start..end].to_vec()
+            }
+            None => self.base.borrow().read_bytes(range).unwrap_or_default(),
+        }
+    }
+}
+
+impl LineSource for HexSource {
+    fn len(&self) -> usize {
+        self.total_bytes().div_ceil(BYTES_PER_LINE as u64) as usize
+    }
+
+    fn lines(&self, range: ops::Range<usize>) -> Vec<String> {
+        let end = cmp::min(range.end, self.len());
+        let start = cmp::min(range.start, end);
+        let bpl = BYTES_PER_LINE as u64;
+        self.read(start as u64 * bpl..end as u64 * bpl)
+            .chunks(BYTES_PER_LINE)
+            .map(format_line)
+            .collect()
+    }
+
+    /// Pick up the bytes appended to the base source.
+    /// The base source must be updated in advance.
+    fn update(&mut self) -> io::Result<SourceChange> {
+        self.encode();
+        Ok(SourceChange::Appended)
+    }
+
+    fn sync(&self) {
+        self.base.borrow().sync();
+    }
+
+    fn is_loading(&self) -> bool {
+        self.base.borrow().is_loading()
+    }
+
+    fn byte_len(&self) -> Option<u64> {
+        Some(self.total_bytes())
+    }
+
+    fn read_bytes(&self, range: ops::Range<u64>) -> Option<Vec<u8>> {
+        Some(self.read(range))
+    }
+
+    fn offset_of_line(&self, n: usize) -> Option<u64> {
+        Some(n as u64 * BYTES_PER_LINE as u64)
+    }
+
+    fn line_at_offset(&self, offset: u64) -> Option<usize> {
+        Some((offset / BYTES_PER_LINE as u64) as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_line() {
+        assert_eq!(
+            format_line(b"0123456789abcde\n"),
+            "3031 3233 3435 3637 3839 6162 6364 650a  0123456789abcde."
+        );
+        assert_eq!(
+            format_line(b"\x00\xffA"),
+            "00ff 41                                  ..A"
+        );
+        assert_eq!(format_line(b""), " ".repeat(HEX_WIDTH + 2));
+        assert_eq!(column_of(0), 0);
+        assert_eq!(column_of(3), 7);
+        assert_eq!(column_of(BYTES_PER_LINE - 1) + 2, HEX_WIDTH);
+    }
+
+    #[test]
+    fn test_parse_offset() {
+        assert_eq!(parse_offset("0x1f0").unwrap(), 0x1f0);
+        assert_eq!(parse_offset(" 496").unwrap(), 496);
+        assert!(parse_offset("1f0").is_err());
+        assert!(parse_offset("").is_err());
+    }
+
+    #[test]
+    fn test_hex_source() {
+        // invalid UTF-8 bytes are encoded back to the raw bytes
+        let line = printable::decode_lossy(b"abcdefghijklmn\xff");
+        let base = Rc::new(RefCell::new(vec![line, "x".to_owned()]));
+        let mut src = HexSource::new(base.clone());
+        assert_eq!(src.byte_len(), Some(18));
+        assert_eq!(src.len(), 2);
+        assert_eq!(src.lines(1..5), vec![format!("{:<41}{}", "780a", "x.")]);
+
+        // the last line is extended and a line is appended
+        base.borrow_mut()[1].push('y');
+        base.borrow_mut().push("z".to_owned());
+        src.update().unwrap();
+        assert_eq!(src.byte_len(), Some(21));
+        assert_eq!(src.line_at_offset(0x1f), Some(1));
+        assert_eq!(src.read_bytes(15..17), Some(b"\nx".to_vec()));
+        assert_eq!(
+            src.line(1),
+            Some(format!("{:<41}{}", "7879 0a7a 0a", "xy.z."))
+        );
+        assert_eq!(src.offset_of_base_line(2), 19);
+        assert_eq!(src.base_line_at_offset(18), 1);
+        assert_eq!(src.base_line_at_offset(19), 2);
+
+        // truncated
+        base.borrow_mut().truncate(1);
+        src.update().unwrap();
+        assert_eq!(src.byte_len(), Some(16));
+        assert_eq!(src.len(), 1);
+    }
+}
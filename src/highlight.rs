@@ -0,0 +1,171 @@
+//! highlight module
+
+use crate::ansi::{self, Highlight};
+use crate::search::{ModalSearcher, Search, SearchMode};
+use std::io;
+
+/// Colors that are given to the patterns without a color (red, yellow, green, cyan, magenta, blue)
+const AUTO_COLORS: [u8; 6] = [1, 3, 2, 6, 5, 4];
+
+const COLOR_NAMES: [&str; 8] = [
+    "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+];
+
+/// Pattern that is always highlighted with the color.
+pub struct HighlightRule {
+    searcher: ModalSearcher,
+    color: String,
+    sgr: String,
+}
+
+impl HighlightRule {
+    /// Return the rule text as `PATTERN=COLOR`.
+    pub fn as_str(&self) -> String {
+        format!("{}={}", self.searcher.as_str(), self.color)
+    }
+}
+
+/// Set of the patterns that are highlighted with their own colors.
+/// The rule added earlier is drawn over the later ones where they overlap.
+#[derive(Default)]
+pub struct HighlightSet {
+    rules: Vec<HighlightRule>,
+}
+
+impl HighlightSet {
+    pub fn new() -> Self {
+        Self { rules: Vec::new() }
+    }
+
+    /// Add the rule of the text `PATTERN[=COLOR]`.
+    /// COLOR is one of the color names, `bright-` and the name, or 0-255.
+    /// If COLOR is omitted or isn't a color, the whole text is the pattern and a color is picked.
+    /// The rule of the same pattern is replaced.
+    pub fn add(&mut self, text: &str, mode: SearchMode) -> io::Result<()> {
+        let (pat, color) = match text.rsplit_once('=') {
+            Some((pat, color)) if !pat.is_empty() && color_sgr(color).is_some() => {
+                (pat, color.to_owned())
+            }
+            _ => (text, self.auto_color()),
+        };
+        let mut searcher = ModalSearcher::with_mode(mode);
+        searcher.set_pattern(pat)?;
+        if searcher.as_str().is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "empty pattern"));
+        }
+        let rule = HighlightRule {
+            searcher,
+            sgr: color_sgr(&color).unwrap(),
+            color,
+        };
+        match self.rules.iter_mut().find(|r| r.searcher.as_str() == pat) {
+            Some(r) => *r = rule,
+            None => self.rules.push(rule),
+        }
+        Ok(())
+    }
+
+    /// Remove the rule of the pattern, and return whether it existed.
+    pub fn remove(&mut self, pat: &str) -> bool {
+        let len = self.rules.len();
+        self.rules.retain(|r| r.searcher.as_str() != pat);
+        self.rules.len() != len
+    }
+
+    pub fn clear(&mut self) {
+        self.rules.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    pub fn rules(&self) -> &[HighlightRule] {
+        &self.rules
+    }
+
+    /// Return the highlight ranges of the rules in the text, one layer per rule.
+    pub fn layers(&self, text: &str) -> Vec<Vec<Highlight>> {
+        self.rules
+            .iter()
+            .map(|r| {
+                r.searcher
+                    .find_iter(text)
+                    .map(|m| Highlight::new(m.start(), m.end(), &r.sgr))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Return the highlight ranges of all rules in the text that don't overlap.
+    pub fn ranges(&self, text: &str) -> Vec<Highlight> {
+        ansi::merge_highlights(self.layers(text))
+    }
+
+    /// Pick the color in turn.
+    fn auto_color(&self) -> String {
+        let n = AUTO_COLORS[self.rules.len() % AUTO_COLORS.len()];
+        COLOR_NAMES[n as usize].to_owned()
+    }
+}
+
+/// Return the SGR sequence of the foreground color.
+fn color_sgr(color: &str) -> Option<String> {
+    let (name, bright) = match color.strip_prefix("bright-") {
+        Some(name) => (name, true),
+        None => (color, false),
+    };
+    if let Some(n) = COLOR_NAMES.iter().position(|&c| c == name) {
+        let base = if bright { 90 } else { 30 };
+        return Some(format!("\x1b[{}m", base + n));
+    }
+    match color.parse::<u8>() {
+        Ok(n) => Some(format!("\x1b[38;5;{}m", n)),
+        Err(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_highlight_set() {
+        let mut set = HighlightSet::new();
+        set.add("ERROR=red", SearchMode::default()).unwrap();
+        set.add("E.*R=bright-blue", SearchMode::default()).unwrap();
+        set.add("id=[0-9]+", SearchMode::default()).unwrap();
+        set.add("WARN=208", SearchMode::default()).unwrap();
+        assert_eq!(
+            set.rules().iter().map(|r| r.as_str()).collect::<Vec<_>>(),
+            vec![
+                "ERROR=red",
+                "E.*R=bright-blue",
+                "id=[0-9]+=green",
+                "WARN=208"
+            ]
+        );
+
+        assert_eq!(
+            set.ranges("xERROR id=42 WARN"),
+            vec![
+                Highlight::new(1, 6, "\x1b[31m"),
+                Highlight::new(6, 16, "\x1b[94m"),
+                Highlight::new(16, 17, "\x1b[38;5;208m"),
+            ]
+        );
+
+        // replaced
+        set.add("ERROR=green", SearchMode::default()).unwrap();
+        assert_eq!(set.rules()[0].as_str(), "ERROR=green");
+
+        assert!(set.remove("E.*R"));
+        assert!(!set.remove("E.*R"));
+        assert_eq!(set.rules().len(), 3);
+
+        assert!(set.add("(=red", SearchMode::default()).is_err());
+        assert!(set.add("", SearchMode::default()).is_err());
+        set.clear();
+        assert!(set.is_empty());
+    }
+}
@@ -0,0 +1,141 @@
+//! history module
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Max number of search patterns kept in the history
+pub const HISTORY_SIZE: usize = 100;
+
+const HISTORY_FILE_NAME: &str = "search_history";
+
+/// Search patterns that were entered, from the oldest to the newest.
+/// Duplicates are dropped and only the newest one is kept.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct History {
+    entries: Vec<String>,
+    capacity: usize,
+}
+
+impl History {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: Vec::new(),
+            capacity,
+        }
+    }
+
+    /// Load the history file. An empty history is returned if the file doesn't exist.
+    pub fn load(path: &Path, capacity: usize) -> io::Result<Self> {
+        let mut history = Self::new(capacity);
+        match fs::read_to_string(path) {
+            Ok(text) => text.lines().for_each(|l| history.push(l)),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        Ok(history)
+    }
+
+    /// Write the history file, one pattern per line.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut text = self.entries.join("\n");
+        text.push('\n');
+        fs::write(path, text)
+    }
+
+    /// Add the pattern as the newest one.
+    /// The oldest ones are dropped if the history is full.
+    pub fn push(&mut self, entry: &str) {
+        if entry.is_empty() {
+            return;
+        }
+        self.entries.retain(|e| e != entry);
+        self.entries.push(entry.to_owned());
+        if self.entries.len() > self.capacity {
+            let excess = self.entries.len() - self.capacity;
+            self.entries.drain(..excess);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Return the pattern of the index. The index 0 is the oldest one.
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.entries.get(index).map(|e| e.as_str())
+    }
+}
+
+/// Return the path of the search history file.
+/// $XDG_STATE_HOME/peep/search_history or ~/.local/state/peep/search_history
+pub fn history_file() -> Option<PathBuf> {
+    env::var_os("XDG_STATE_HOME")
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|h| PathBuf::from(h).join(".local/state")))
+        .map(|d| d.join("peep").join(HISTORY_FILE_NAME))
+}
+
+/// Add the pattern to the history file.
+/// The file is read again, so that patterns saved by other sessions are kept.
+pub fn append(path: &Path, entry: &str, capacity: usize) -> io::Result<()> {
+    let mut history = History::load(path, capacity)?;
+    history.push(entry);
+    history.save(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_push() {
+        let mut history = History::new(3);
+        history.push("a");
+        history.push("b");
+        history.push("");
+        history.push("a");
+        assert_eq!(history.len(), 2);
+        assert_eq!(history.get(0), Some("b"));
+        assert_eq!(history.get(1), Some("a"));
+
+        history.push("c");
+        history.push("d");
+        assert_eq!(history.len(), 3);
+        assert_eq!(history.get(0), Some("a"));
+        assert_eq!(history.get(2), Some("d"));
+        assert_eq!(history.get(3), None);
+    }
+
+    #[test]
+    fn test_load_and_save() {
+        let mut path = env::temp_dir();
+        path.push(format!("peep-history-{}", std::process::id()));
+        let path = path.join("search_history");
+
+        assert!(History::load(&path, 10).unwrap().is_empty());
+        append(&path, "error", 10).unwrap();
+        append(&path, "warn", 10).unwrap();
+        append(&path, "error", 10).unwrap();
+        let history = History::load(&path, 10).unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history.get(0), Some("warn"));
+        assert_eq!(history.get(1), Some("error"));
+
+        // capped on load
+        let history = History::load(&path, 1).unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history.get(0), Some("error"));
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,251 @@
+//! input module
+
+use crate::keybind::Key;
+use std::os::unix::io::RawFd;
+use std::str;
+
+/// Time to wait for the rest of an escape sequence
+pub const ESC_TIMEOUT_MS: i32 = 100;
+
+const ESC: u8 = 0x1b;
+
+/// Key input decoder
+///
+/// Bytes of CSI (`ESC [`) and SS3 (`ESC O`) key sequences are decoded into logical keys.
+/// A lone ESC is decoded when the rest of the sequence doesn't arrive in time.
+/// The other bytes are decoded as UTF-8 chars.
+#[derive(Default)]
+pub struct KeyDecoder {
+    pending: Vec<u8>,
+    utf8: Vec<u8>,
+}
+
+impl KeyDecoder {
+    pub fn new() -> Self {
+        Self {
+            pending: Vec::with_capacity(16),
+            utf8: Vec::with_capacity(4),
+        }
+    }
+
+    /// Return whether the decoder waits for the rest of a sequence.
+    pub fn is_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// Feed a byte and return the decoded keys.
+    pub fn feed(&mut self, b: u8) -> Vec<Key> {
+        if self.pending.is_empty() {
+            if b == ESC {
+                // an incomplete char is discarded
+                self.utf8.clear();
+                self.pending.push(b);
+                return vec![];
+            }
+            return self.decode_utf8(b).into_iter().collect();
+        }
+
+        self.pending.push(b);
+        match self.pending[1] {
+            b'[' if self.pending.len() == 2 => vec![],
+            b'[' => {
+                // parameter and intermediate bytes continue until the final byte
+                if (0x20..=0x3f).contains(&b) {
+                    return vec![];
+                }
+                let seq: Vec<u8> = self.pending.drain(..).collect();
+                csi_key(&seq[2..]).into_iter().collect()
+            }
+            b'O' if self.pending.len() == 2 => vec![],
+            b'O' => {
+                self.pending.clear();
+                ss3_key(b).into_iter().collect()
+            }
+            _ => {
+                // not a key sequence, ESC is typed alone
+                self.pending.clear();
+                let mut keys = vec![Key::Char(ESC as char)];
+                keys.extend(self.feed(b));
+                keys
+            }
+        }
+    }
+
+    /// Decode UTF-8 bytes. Return the char when its last byte arrives.
+    fn decode_utf8(&mut self, b: u8) -> Option<Key> {
+        if self.utf8.is_empty() && b.is_ascii() {
+            return Some(Key::Char(b as char));
+        }
+        self.utf8.push(b);
+        match str::from_utf8(&self.utf8) {
+            Ok(s) => {
+                let c = s.chars().next();
+                self.utf8.clear();
+                c.map(Key::Char)
+            }
+            // wait for the rest of bytes
+            Err(e) if e.error_len().is_none() => None,
+            Err(_) => {
+                // invalid sequence is dropped, but the ascii byte is kept
+                self.utf8.clear();
+                if b.is_ascii() {
+                    Some(Key::Char(b as char))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    /// Give up waiting for the rest of the sequence and return the pending bytes as keys.
+    pub fn flush(&mut self) -> Vec<Key> {
+        self.pending
+            .drain(..)
+            .map(|b| Key::Char(b as char))
+            .collect()
+    }
+}
+
+/// Decode CSI sequence that is followed by `ESC [`.
+fn csi_key(seq: &[u8]) -> Option<Key> {
+    let (&last, params) = seq.split_last()?;
+    if let Some(params) = params.strip_prefix(b"<") {
+        return mouse_key(params, last);
+    }
+    // modifiers like "1;5" are ignored
+    let first_param = params.split(|&b| b == b';').next().unwrap_or(&[]);
+    match last {
+        b'A' => Some(Key::Up),
+        b'B' => Some(Key::Down),
+        b'C' => Some(Key::Right),
+        b'D' => Some(Key::Left),
+        b'H' => Some(Key::Home),
+        b'F' => Some(Key::End),
+        b'~' => match first_param {
+            b"1" | b"7" => Some(Key::Home),
+            b"4" | b"8" => Some(Key::End),
+            b"5" => Some(Key::PageUp),
+            b"6" => Some(Key::PageDown),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Decode SGR mouse report `ESC [ < button ; column ; row M`.
+/// Only wheel and left button press are decoded.
+fn mouse_key(params: &[u8], last: u8) -> Option<Key> {
+    let mut nums = params
+        .split(|&b| b == b';')
+        .map(|p| str::from_utf8(p).ok().and_then(|p| p.parse::<u16>().ok()));
+    let (button, x, y) = (nums.next()??, nums.next()??, nums.next()??);
+    // shift, meta and control modifiers are ignored
+    match (button & !0x1c, last) {
+        (64, b'M') => Some(Key::WheelUp),
+        (65, b'M') => Some(Key::WheelDown),
+        (0, b'M') => Some(Key::Click(x, y)),
+        _ => None,
+    }
+}
+
+/// Decode SS3 sequence that is followed by `ESC O`.
+fn ss3_key(b: u8) -> Option<Key> {
+    match b {
+        b'A' => Some(Key::Up),
+        b'B' => Some(Key::Down),
+        b'C' => Some(Key::Right),
+        b'D' => Some(Key::Left),
+        b'H' => Some(Key::Home),
+        b'F' => Some(Key::End),
+        _ => None,
+    }
+}
+
+/// Wait until the fd becomes readable. Return false on timeout.
+pub fn wait_readable(fd: RawFd, timeout_ms: i32) -> bool {
+    use nix::poll::{poll, PollFd, PollFlags};
+
+    let mut fds = [PollFd::new(fd, PollFlags::POLLIN)];
+    matches!(poll(&mut fds, timeout_ms), Ok(n) if n > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(bytes: &[u8]) -> Vec<Key> {
+        let mut dec = KeyDecoder::new();
+        bytes.iter().flat_map(|&b| dec.feed(b)).collect()
+    }
+
+    #[test]
+    fn test_decode_sequences() {
+        assert_eq!(decode(b"j"), vec![Key::Char('j')]);
+        assert_eq!(decode(b"\x1b[A\x1b[B"), vec![Key::Up, Key::Down]);
+        assert_eq!(decode(b"\x1bOC\x1bOD"), vec![Key::Right, Key::Left]);
+        assert_eq!(decode(b"\x1b[5~\x1b[6~"), vec![Key::PageUp, Key::PageDown]);
+        assert_eq!(
+            decode(b"\x1b[H\x1b[F\x1b[1~\x1b[4~\x1bOH\x1bOF"),
+            vec![
+                Key::Home,
+                Key::End,
+                Key::Home,
+                Key::End,
+                Key::Home,
+                Key::End
+            ]
+        );
+        // with modifiers
+        assert_eq!(decode(b"\x1b[1;5A"), vec![Key::Up]);
+        // unknown sequences are dropped
+        assert_eq!(decode(b"\x1b[2~\x1b[Zq"), vec![Key::Char('q')]);
+        // ESC followed by another key
+        assert_eq!(decode(b"\x1bj"), vec![Key::Char('\x1b'), Key::Char('j')]);
+        assert_eq!(decode(b"\x1b\x1b[A"), vec![Key::Char('\x1b'), Key::Up]);
+    }
+
+    #[test]
+    fn test_decode_mouse() {
+        assert_eq!(
+            decode(b"\x1b[<64;10;5M\x1b[<65;10;5M"),
+            vec![Key::WheelUp, Key::WheelDown]
+        );
+        assert_eq!(
+            decode(b"\x1b[<0;3;24M\x1b[<0;3;24m"),
+            vec![Key::Click(3, 24)]
+        );
+        // right button and motion are dropped
+        assert_eq!(decode(b"\x1b[<2;1;1M\x1b[<32;1;1M\x1b[<0;1M"), vec![]);
+    }
+
+    #[test]
+    fn test_decode_utf8() {
+        assert_eq!(
+            decode("aé検索".as_bytes()),
+            vec![
+                Key::Char('a'),
+                Key::Char('é'),
+                Key::Char('検'),
+                Key::Char('索')
+            ]
+        );
+        assert_eq!(decode("🦀".as_bytes()), vec![Key::Char('🦀')]);
+        // invalid bytes are dropped
+        assert_eq!(decode(b"\xe6\x9cj\xff"), vec![Key::Char('j')]);
+        // ESC interrupts the incomplete char
+        assert_eq!(decode(b"\xe6\x1b[A"), vec![Key::Up]);
+    }
+
+    #[test]
+    fn test_flush() {
+        let mut dec = KeyDecoder::new();
+        assert!(dec.feed(0x1b).is_empty());
+        assert!(dec.is_pending());
+        assert_eq!(dec.flush(), vec![Key::Char('\x1b')]);
+        assert!(!dec.is_pending());
+
+        assert!(dec.feed(0x1b).is_empty());
+        assert!(dec.feed(b'[').is_empty());
+        assert_eq!(dec.flush(), vec![Key::Char('\x1b'), Key::Char('[')]);
+    }
+}
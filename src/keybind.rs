@@ -1,65 +1,126 @@
 /// Key Bind Parser
 use crate::event::PeepEvent;
+use crate::history::{History, HISTORY_SIZE};
+use std::sync::{Arc, Mutex};
+
+/// Logical key decoded from the input
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Key {
+    Char(char),
+    Up,
+    Down,
+    Left,
+    Right,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    WheelUp,
+    WheelDown,
+    /// Left click at (column, row)
+    Click(u16, u16),
+}
 
 pub trait KeyParser {
-    fn parse(&mut self, c: char) -> Option<PeepEvent>;
+    fn parse(&mut self, key: Key) -> Option<PeepEvent>;
 }
 
 /// Default key map
 pub mod default {
     use super::*;
+    use std::cmp;
     use std::collections::HashMap;
 
-    const ALLOWED_CTRL_KEYCODES: [char; 10] = [
-        /* Ctr-a = */ '\x01', /* Ctr-b = */ '\x02', /* Ctr-d = */ '\x04',
-        /* Ctr-e = */ '\x05', /* Ctr-f = */ '\x06', /* Ctr-j = */ '\x0a',
-        /* Ctr-k = */ '\x0b', /* Ctr-n = */ '\x0e', /* Ctr-p = */ '\x10',
-        /* Ctr-u = */ '\x15',
-    ];
-
     // Ready -> IncSearching
     // IncSearching -> Ready
     //
+    // Ready -> Filtering
+    // Filtering -> Ready
+    //
     // Ready -> Numbering
     // Numbering -> Ready : cancel
     // Numbering -> Commanding
     //
     // Ready -> Commanding
     // Commanding -> Ready
+    // Commanding -> Highlighting
+    // Highlighting -> Ready
+    //
+    // Commanding -> Offsetting
+    // Offsetting -> Ready
+    //
+    // Commanding -> Marking
+    // Marking -> Ready
     enum State {
         Ready,
         IncSearching,
+        Filtering,
         Numbering,
         Commanding,
+        Highlighting,
+        Offsetting,
+        // waiting for the name of the mark to set or to jump to
+        Marking { jumps: bool },
     }
 
-    pub struct KeyBind<'a> {
+    pub struct KeyBind {
         state: State,
-        number: u16,
+        number: usize,
         wip_keys: String,
-        cmap: HashMap<&'a str, PeepEvent>,
+        wip_cmd: Vec<Key>,
+        cmap: HashMap<Vec<Key>, PeepEvent>,
+        // control keys that are used in the command table
+        ctrl_keys: Vec<char>,
+        // shared with the app, which adds the accepted patterns
+        history: Arc<Mutex<History>>,
+        // index of the recalled pattern, or None while typing a new one
+        history_pos: Option<usize>,
+        // pattern being typed before the history is recalled
+        draft: String,
     }
 
-    impl<'a> Default for KeyBind<'a> {
+    impl Default for KeyBind {
         fn default() -> Self {
             Self::new()
         }
     }
 
-    impl<'a> KeyBind<'a> {
+    impl KeyBind {
         pub fn new() -> Self {
-            let mut kb = KeyBind {
+            Self::with_command_table(Self::default_command_table())
+        }
+
+        /// Create key bind with the command table instead of the default one.
+        pub fn with_command_table(cmap: HashMap<Vec<Key>, PeepEvent>) -> Self {
+            let mut ctrl_keys: Vec<char> = cmap
+                .keys()
+                .flatten()
+                .filter_map(|k| match *k {
+                    Key::Char(c) if c.is_control() => Some(c),
+                    _ => None,
+                })
+                .collect();
+            ctrl_keys.sort_unstable();
+            ctrl_keys.dedup();
+            KeyBind {
                 state: State::Ready,
                 number: 0,
                 wip_keys: String::with_capacity(64),
-                cmap: HashMap::new(),
-            };
-            kb.cmap = KeyBind::default_command_table();
-            kb
+                wip_cmd: Vec::with_capacity(8),
+                cmap,
+                ctrl_keys,
+                history: Arc::new(Mutex::new(History::new(HISTORY_SIZE))),
+                history_pos: None,
+                draft: String::new(),
+            }
         }
 
-        fn default_command_table() -> HashMap<&'a str, PeepEvent> {
-            // let mut default: HashMap<&str, PeepEvent> = [
+        /// Set the search history that is recalled by Up and Down keys.
+        pub fn set_history(&mut self, history: Arc<Mutex<History>>) {
+            self.history = history;
+        }
+
+        pub fn default_command_table() -> HashMap<Vec<Key>, PeepEvent> {
             [
                 ("j", PeepEvent::MoveDown(1)),
                 (/* Ctr-j */ "\x0a", PeepEvent::MoveDown(1)),
@@ -96,58 +157,126 @@ pub mod default {
                 ("q", PeepEvent::Quit),
                 ("Q", PeepEvent::QuitWithClear),
                 ("F", PeepEvent::FollowMode),
+                (":n", PeepEvent::NextFile),
+                (":p", PeepEvent::PrevFile),
+                (":i", PeepEvent::ToggleSmartCase),
+                (":I", PeepEvent::ToggleIgnoreCase),
+                (":F", PeepEvent::ToggleFixedString),
+                (":h", PeepEvent::HighlightIncremental(String::new())),
+                (":l", PeepEvent::CycleLogLevel),
+                (":x", PeepEvent::ToggleHexView),
+                (":o", PeepEvent::GotoOffsetIncremental(String::new())),
+                ("m", PeepEvent::SetMark('\0')),
+                ("'", PeepEvent::JumpToMark('\0')),
             ]
             .iter()
-            .cloned()
+            .map(|(k, v)| (k.chars().map(Key::Char).collect(), v.clone()))
+            .chain(
+                [
+                    (Key::Down, PeepEvent::MoveDown(1)),
+                    (Key::Up, PeepEvent::MoveUp(1)),
+                    (Key::Left, PeepEvent::MoveLeft(1)),
+                    (Key::Right, PeepEvent::MoveRight(1)),
+                    (Key::PageDown, PeepEvent::MoveDownPages(1)),
+                    (Key::PageUp, PeepEvent::MoveUpPages(1)),
+                    (Key::Home, PeepEvent::MoveToTopOfLines),
+                    (Key::End, PeepEvent::MoveToBottomOfLines),
+                    (Key::WheelDown, PeepEvent::MoveDown(1)),
+                    (Key::WheelUp, PeepEvent::MoveUp(1)),
+                ]
+                .iter()
+                .map(|(k, v)| (vec![*k], v.clone())),
+            )
             .collect()
         }
 
+        fn is_command_key(&self, key: Key) -> bool {
+            match key {
+                Key::Char(c) => !c.is_control() || self.ctrl_keys.contains(&c),
+                _ => true,
+            }
+        }
+
         fn trans_to_ready(&mut self) {
             self.state = State::Ready;
             self.number = 0;
             self.wip_keys.clear();
+            self.wip_cmd.clear();
         }
         fn trans_to_incsearching(&mut self) {
             self.state = State::IncSearching;
             self.number = 0;
             self.wip_keys.clear();
+            self.history_pos = None;
+        }
+        fn trans_to_filtering(&mut self) {
+            self.state = State::Filtering;
+            self.number = 0;
+            self.wip_keys.clear();
+        }
+        fn trans_to_highlighting(&mut self) {
+            self.state = State::Highlighting;
+            self.number = 0;
+            self.wip_keys.clear();
+        }
+        fn trans_to_offsetting(&mut self) {
+            self.state = State::Offsetting;
+            self.number = 0;
+            self.wip_keys.clear();
+        }
+        fn trans_to_marking(&mut self, jumps: bool) {
+            self.state = State::Marking { jumps };
+            self.number = 0;
+            self.wip_cmd.clear();
         }
         fn trans_to_numbering(&mut self, c: char) {
             self.state = State::Numbering;
-            self.number = c.to_digit(10).unwrap() as u16;
+            self.number = c.to_digit(10).unwrap() as usize;
             self.wip_keys.push(c);
         }
         fn trans_to_commanding(&mut self) {
             self.state = State::Commanding;
             self.wip_keys.clear();
+            self.wip_cmd.clear();
         }
 
-        fn action_ready(&mut self, c: char) -> Option<PeepEvent> {
-            match c {
-                '/' => {
+        fn action_ready(&mut self, key: Key) -> Option<PeepEvent> {
+            match key {
+                Key::Char('/') => {
                     self.trans_to_incsearching();
                     Some(PeepEvent::SearchIncremental("".to_owned()))
                 }
-                '1'..='9' => {
+                Key::Char('&') => {
+                    self.trans_to_filtering();
+                    Some(PeepEvent::FilterIncremental("".to_owned()))
+                }
+                Key::Char(c @ '1'..='9') => {
                     self.trans_to_numbering(c);
                     // Some(PeepEvent::Message(Some(self.number.to_string())))
                     None
                 }
-                c if !c.is_control() | ALLOWED_CTRL_KEYCODES.contains(&c) => {
+                k if self.is_command_key(k) => {
                     self.trans_to_commanding();
-                    self.action_commanding(c)
+                    self.action_commanding(k)
                 }
                 // ESC
-                '\x1b' => Some(PeepEvent::Cancel),
+                Key::Char('\x1b') => Some(PeepEvent::Cancel),
                 _ => None,
             }
         }
 
-        fn action_incsearching(&mut self, c: char) -> Option<PeepEvent> {
+        fn action_incsearching(&mut self, key: Key) -> Option<PeepEvent> {
+            let c = match key {
+                Key::Char(c) => c,
+                Key::Up | Key::Down if matches!(self.state, State::IncSearching) => {
+                    return self.recall_history(key == Key::Up);
+                }
+                _ => return None,
+            };
             match c {
                 c if !c.is_control() => {
                     self.wip_keys.push(c);
-                    Some(PeepEvent::SearchIncremental(self.wip_keys.to_owned()))
+                    Some(self.typing_event())
                 }
                 '\x08' | '\x7f' => {
                     // BackSpace, Delete
@@ -155,13 +284,21 @@ pub mod default {
                         self.trans_to_ready();
                         Some(PeepEvent::Cancel)
                     } else {
-                        Some(PeepEvent::SearchIncremental(self.wip_keys.to_owned()))
+                        Some(self.typing_event())
                     }
                 }
                 '\n' => {
                     // LF
+                    let event = match self.state {
+                        State::Filtering => PeepEvent::FilterTrigger(self.wip_keys.to_owned()),
+                        State::Highlighting => {
+                            PeepEvent::HighlightTrigger(self.wip_keys.to_owned())
+                        }
+                        State::Offsetting => PeepEvent::GotoOffsetTrigger(self.wip_keys.to_owned()),
+                        _ => PeepEvent::SearchTrigger,
+                    };
                     self.trans_to_ready();
-                    Some(PeepEvent::SearchTrigger)
+                    Some(event)
                 }
                 '\x1b' => {
                     // ESC -> Cancel
@@ -172,16 +309,60 @@ pub mod default {
             }
         }
 
-        fn action_numbering(&mut self, c: char) -> Option<PeepEvent> {
+        /// Replace the typing pattern with the older or newer one in the history.
+        /// Going newer than the newest one restores the pattern being typed.
+        fn recall_history(&mut self, older: bool) -> Option<PeepEvent> {
+            let history = self.history.clone();
+            let history = history.lock().unwrap();
+            let pos = match (self.history_pos, older) {
+                (None, true) if !history.is_empty() => {
+                    self.draft = self.wip_keys.clone();
+                    Some(history.len() - 1)
+                }
+                (Some(i), true) => Some(i.saturating_sub(1)),
+                (Some(i), false) if i + 1 < history.len() => Some(i + 1),
+                (Some(_), false) => None,
+                (None, _) => return None,
+            };
+            self.history_pos = pos;
+            self.wip_keys = match pos {
+                Some(i) => history.get(i).unwrap_or_default().to_owned(),
+                None => self.draft.clone(),
+            };
+            Some(self.typing_event())
+        }
+
+        /// Return the event of the text being typed on the prompt.
+        fn typing_event(&self) -> PeepEvent {
+            match self.state {
+                State::Filtering => PeepEvent::FilterIncremental(self.wip_keys.to_owned()),
+                State::Highlighting => PeepEvent::HighlightIncremental(self.wip_keys.to_owned()),
+                State::Offsetting => PeepEvent::GotoOffsetIncremental(self.wip_keys.to_owned()),
+                _ => PeepEvent::SearchIncremental(self.wip_keys.to_owned()),
+            }
+        }
+
+        fn action_numbering(&mut self, key: Key) -> Option<PeepEvent> {
+            let c = match key {
+                Key::Char(c) => c,
+                // special keys take the count
+                k => {
+                    self.trans_to_commanding();
+                    return self.action_commanding(k);
+                }
+            };
             match c {
                 '0'..='9' => {
-                    self.number = self.number * 10 + c.to_digit(10).unwrap() as u16;
+                    self.number = self
+                        .number
+                        .saturating_mul(10)
+                        .saturating_add(c.to_digit(10).unwrap() as usize);
                     // Some(PeepEvent::Message(Some(self.number.to_string())))
                     None
                 }
                 c if !c.is_control() => {
                     self.trans_to_commanding();
-                    self.action_commanding(c)
+                    self.action_commanding(key)
                 }
                 '\x1b' | '\n' => {
                     // ESC and LF -> Cancel
@@ -193,18 +374,36 @@ pub mod default {
             }
         }
 
-        fn action_commanding(&mut self, c: char) -> Option<PeepEvent> {
+        fn action_commanding(&mut self, key: Key) -> Option<PeepEvent> {
             let mut needs_trans = false;
-            let op = match c {
-                c if !c.is_control() | ALLOWED_CTRL_KEYCODES.contains(&c) => {
-                    self.wip_keys.push(c);
-                    match self.cmap.get::<str>(&self.wip_keys) {
+            let op = match key {
+                k if self.is_command_key(k) => {
+                    self.wip_cmd.push(k);
+                    match self.cmap.get(&self.wip_cmd) {
+                        Some(PeepEvent::HighlightIncremental(_)) => {
+                            // the command opens the prompt of highlight patterns
+                            self.trans_to_highlighting();
+                            return Some(PeepEvent::HighlightIncremental(String::new()));
+                        }
+                        Some(PeepEvent::GotoOffsetIncremental(_)) => {
+                            // the command opens the prompt of the offset
+                            self.trans_to_offsetting();
+                            return Some(PeepEvent::GotoOffsetIncremental(String::new()));
+                        }
+                        Some(PeepEvent::SetMark(_)) => {
+                            self.trans_to_marking(false);
+                            return None;
+                        }
+                        Some(PeepEvent::JumpToMark(_)) => {
+                            self.trans_to_marking(true);
+                            return None;
+                        }
                         Some(v) => {
                             needs_trans = true;
                             self.combine_command(v.to_owned())
                         }
                         None => {
-                            if self.cmap.keys().any(|&k| k.starts_with(&self.wip_keys)) {
+                            if self.cmap.keys().any(|k| k.starts_with(&self.wip_cmd)) {
                                 // has candidates
                                 None
                             } else {
@@ -226,8 +425,23 @@ pub mod default {
             op
         }
 
+        fn action_marking(&mut self, key: Key, jumps: bool) -> Option<PeepEvent> {
+            self.trans_to_ready();
+            match key {
+                Key::Char('\x1b') => Some(PeepEvent::Cancel),
+                Key::Char(c) if !c.is_control() => Some(if jumps {
+                    PeepEvent::JumpToMark(c)
+                } else {
+                    PeepEvent::SetMark(c)
+                }),
+                _ => Some(PeepEvent::Message(None)),
+            }
+        }
+
         fn combine_command(&self, op: PeepEvent) -> Option<PeepEvent> {
             let valid_num = |n| if n == 0 { 1 } else { n };
+            // pane height is bounded by the terminal rows
+            let valid_rows = |n: usize| cmp::min(cmp::max(n, 1), u16::MAX as usize) as u16;
             match op {
                 PeepEvent::MoveDown(_) => Some(PeepEvent::MoveDown(valid_num(self.number))),
                 PeepEvent::MoveUp(_) => Some(PeepEvent::MoveUp(valid_num(self.number))),
@@ -260,35 +474,51 @@ pub mod default {
                     Some(PeepEvent::MoveToLineNumber(valid_num(self.number)))
                 }
                 PeepEvent::IncrementLines(_) => {
-                    Some(PeepEvent::IncrementLines(valid_num(self.number)))
+                    Some(PeepEvent::IncrementLines(valid_rows(self.number)))
                 }
                 PeepEvent::DecrementLines(_) => {
-                    Some(PeepEvent::DecrementLines(valid_num(self.number)))
+                    Some(PeepEvent::DecrementLines(valid_rows(self.number)))
                 }
                 PeepEvent::SetNumOfLines(_) => {
                     if self.number == 0 {
                         None
                     } else {
-                        Some(PeepEvent::SetNumOfLines(self.number))
+                        Some(PeepEvent::SetNumOfLines(valid_rows(self.number)))
                     }
                 }
                 _ => Some(op),
             }
         }
 
-        fn trans(&mut self, c: char) -> Option<PeepEvent> {
+        fn trans(&mut self, key: Key) -> Option<PeepEvent> {
+            if let Key::Click(x, y) = key {
+                return match self.state {
+                    State::IncSearching
+                    | State::Filtering
+                    | State::Highlighting
+                    | State::Offsetting => None,
+                    _ => {
+                        self.trans_to_ready();
+                        Some(PeepEvent::MouseClick(x, y))
+                    }
+                };
+            }
             match self.state {
-                State::Ready => self.action_ready(c),
-                State::IncSearching => self.action_incsearching(c),
-                State::Numbering => self.action_numbering(c),
-                State::Commanding => self.action_commanding(c),
+                State::Ready => self.action_ready(key),
+                State::IncSearching
+                | State::Filtering
+                | State::Highlighting
+                | State::Offsetting => self.action_incsearching(key),
+                State::Numbering => self.action_numbering(key),
+                State::Commanding => self.action_commanding(key),
+                State::Marking { jumps } => self.action_marking(key, jumps),
             }
         }
     }
 
-    impl<'a> KeyParser for KeyBind<'a> {
-        fn parse(&mut self, c: char) -> Option<PeepEvent> {
-            self.trans(c)
+    impl KeyParser for KeyBind {
+        fn parse(&mut self, key: Key) -> Option<PeepEvent> {
+            self.trans(key)
         }
     }
 }
@@ -297,46 +527,84 @@ pub mod default {
 mod tests {
     use super::*;
     use crate::event::PeepEvent;
+    use crate::history::{History, HISTORY_SIZE};
 
     #[test]
     fn test_keybind_command() {
         let mut kb = default::KeyBind::new();
 
         // normal commands
-        assert_eq!(kb.parse('j'), Some(PeepEvent::MoveDown(1)));
-        assert_eq!(kb.parse('\x0a'), Some(PeepEvent::MoveDown(1)));
-        assert_eq!(kb.parse('\x0e'), Some(PeepEvent::MoveDown(1)));
-        assert_eq!(kb.parse('k'), Some(PeepEvent::MoveUp(1)));
-        assert_eq!(kb.parse('\x0b'), Some(PeepEvent::MoveUp(1)));
-        assert_eq!(kb.parse('\x10'), Some(PeepEvent::MoveUp(1)));
-        assert_eq!(kb.parse('h'), Some(PeepEvent::MoveLeft(1)));
-        assert_eq!(kb.parse('l'), Some(PeepEvent::MoveRight(1)));
-        assert_eq!(kb.parse('d'), Some(PeepEvent::MoveDownHalfPages(1)));
-        assert_eq!(kb.parse('\x04'), Some(PeepEvent::MoveDownHalfPages(1)));
-        assert_eq!(kb.parse('u'), Some(PeepEvent::MoveUpHalfPages(1)));
-        assert_eq!(kb.parse('\x15'), Some(PeepEvent::MoveUpHalfPages(1)));
-        assert_eq!(kb.parse('f'), Some(PeepEvent::MoveDownPages(1)));
-        assert_eq!(kb.parse('\x06'), Some(PeepEvent::MoveDownPages(1)));
-        assert_eq!(kb.parse(' '), Some(PeepEvent::MoveDownPages(1)));
-        assert_eq!(kb.parse('b'), Some(PeepEvent::MoveUpPages(1)));
-        assert_eq!(kb.parse('\x02'), Some(PeepEvent::MoveUpPages(1)));
-        assert_eq!(kb.parse('0'), Some(PeepEvent::MoveToHeadOfLine));
-        assert_eq!(kb.parse('\x01'), Some(PeepEvent::MoveToHeadOfLine));
-        assert_eq!(kb.parse('$'), Some(PeepEvent::MoveToEndOfLine));
-        assert_eq!(kb.parse('\x05'), Some(PeepEvent::MoveToEndOfLine));
-        assert_eq!(kb.parse('g'), Some(PeepEvent::MoveToTopOfLines));
-        assert_eq!(kb.parse('G'), Some(PeepEvent::MoveToBottomOfLines));
-        assert_eq!(kb.parse('-'), Some(PeepEvent::DecrementLines(1)));
-        assert_eq!(kb.parse('+'), Some(PeepEvent::IncrementLines(1)));
-        assert_eq!(kb.parse('='), None);
-        assert_eq!(kb.parse('n'), Some(PeepEvent::SearchNext));
-        assert_eq!(kb.parse('N'), Some(PeepEvent::SearchPrev));
-        assert_eq!(kb.parse('q'), Some(PeepEvent::Quit));
-        assert_eq!(kb.parse('Q'), Some(PeepEvent::QuitWithClear));
-        assert_eq!(kb.parse('#'), Some(PeepEvent::ToggleLineNumberPrinting));
-        assert_eq!(kb.parse('!'), Some(PeepEvent::ToggleLineWraps));
-        assert_eq!(kb.parse('F'), Some(PeepEvent::FollowMode));
-        assert_eq!(kb.parse('\x1b'), Some(PeepEvent::Cancel));
+        assert_eq!(kb.parse(Key::Char('j')), Some(PeepEvent::MoveDown(1)));
+        assert_eq!(kb.parse(Key::Char('\x0a')), Some(PeepEvent::MoveDown(1)));
+        assert_eq!(kb.parse(Key::Char('\x0e')), Some(PeepEvent::MoveDown(1)));
+        assert_eq!(kb.parse(Key::Char('k')), Some(PeepEvent::MoveUp(1)));
+        assert_eq!(kb.parse(Key::Char('\x0b')), Some(PeepEvent::MoveUp(1)));
+        assert_eq!(kb.parse(Key::Char('\x10')), Some(PeepEvent::MoveUp(1)));
+        assert_eq!(kb.parse(Key::Char('h')), Some(PeepEvent::MoveLeft(1)));
+        assert_eq!(kb.parse(Key::Char('l')), Some(PeepEvent::MoveRight(1)));
+        assert_eq!(
+            kb.parse(Key::Char('d')),
+            Some(PeepEvent::MoveDownHalfPages(1))
+        );
+        assert_eq!(
+            kb.parse(Key::Char('\x04')),
+            Some(PeepEvent::MoveDownHalfPages(1))
+        );
+        assert_eq!(
+            kb.parse(Key::Char('u')),
+            Some(PeepEvent::MoveUpHalfPages(1))
+        );
+        assert_eq!(
+            kb.parse(Key::Char('\x15')),
+            Some(PeepEvent::MoveUpHalfPages(1))
+        );
+        assert_eq!(kb.parse(Key::Char('f')), Some(PeepEvent::MoveDownPages(1)));
+        assert_eq!(
+            kb.parse(Key::Char('\x06')),
+            Some(PeepEvent::MoveDownPages(1))
+        );
+        assert_eq!(kb.parse(Key::Char(' ')), Some(PeepEvent::MoveDownPages(1)));
+        assert_eq!(kb.parse(Key::Char('b')), Some(PeepEvent::MoveUpPages(1)));
+        assert_eq!(kb.parse(Key::Char('\x02')), Some(PeepEvent::MoveUpPages(1)));
+        assert_eq!(kb.parse(Key::Char('0')), Some(PeepEvent::MoveToHeadOfLine));
+        assert_eq!(
+            kb.parse(Key::Char('\x01')),
+            Some(PeepEvent::MoveToHeadOfLine)
+        );
+        assert_eq!(kb.parse(Key::Char('$')), Some(PeepEvent::MoveToEndOfLine));
+        assert_eq!(
+            kb.parse(Key::Char('\x05')),
+            Some(PeepEvent::MoveToEndOfLine)
+        );
+        assert_eq!(kb.parse(Key::Char('g')), Some(PeepEvent::MoveToTopOfLines));
+        assert_eq!(
+            kb.parse(Key::Char('G')),
+            Some(PeepEvent::MoveToBottomOfLines)
+        );
+        assert_eq!(kb.parse(Key::Char('-')), Some(PeepEvent::DecrementLines(1)));
+        assert_eq!(kb.parse(Key::Char('+')), Some(PeepEvent::IncrementLines(1)));
+        assert_eq!(kb.parse(Key::Char('=')), None);
+        assert_eq!(kb.parse(Key::Char('n')), Some(PeepEvent::SearchNext));
+        assert_eq!(kb.parse(Key::Char('N')), Some(PeepEvent::SearchPrev));
+        assert_eq!(kb.parse(Key::Char('q')), Some(PeepEvent::Quit));
+        assert_eq!(kb.parse(Key::Char('Q')), Some(PeepEvent::QuitWithClear));
+        assert_eq!(
+            kb.parse(Key::Char('#')),
+            Some(PeepEvent::ToggleLineNumberPrinting)
+        );
+        assert_eq!(kb.parse(Key::Char('!')), Some(PeepEvent::ToggleLineWraps));
+        assert_eq!(kb.parse(Key::Char('F')), Some(PeepEvent::FollowMode));
+        assert_eq!(kb.parse(Key::Char(':')), None);
+        assert_eq!(kb.parse(Key::Char('n')), Some(PeepEvent::NextFile));
+        assert_eq!(kb.parse(Key::Char(':')), None);
+        assert_eq!(kb.parse(Key::Char('p')), Some(PeepEvent::PrevFile));
+        assert_eq!(kb.parse(Key::Char(':')), None);
+        assert_eq!(kb.parse(Key::Char('i')), Some(PeepEvent::ToggleSmartCase));
+        assert_eq!(kb.parse(Key::Char(':')), None);
+        assert_eq!(kb.parse(Key::Char('I')), Some(PeepEvent::ToggleIgnoreCase));
+        assert_eq!(kb.parse(Key::Char(':')), None);
+        assert_eq!(kb.parse(Key::Char('F')), Some(PeepEvent::ToggleFixedString));
+        assert_eq!(kb.parse(Key::Char('\x1b')), Some(PeepEvent::Cancel));
     }
 
     #[test]
@@ -344,24 +612,211 @@ mod tests {
         let mut kb = default::KeyBind::new();
 
         // normal commands
-        assert_eq!(kb.parse('1'), None);
-        assert_eq!(kb.parse('2'), None);
-        assert_eq!(kb.parse('\n'), None);
+        assert_eq!(kb.parse(Key::Char('1')), None);
+        assert_eq!(kb.parse(Key::Char('2')), None);
+        assert_eq!(kb.parse(Key::Char('\n')), None);
+
+        assert_eq!(kb.parse(Key::Char('1')), None);
+        assert_eq!(kb.parse(Key::Char('2')), None);
+        assert_eq!(kb.parse(Key::Char('\x1b')), None);
+
+        assert_eq!(kb.parse(Key::Char('2')), None);
+        assert_eq!(kb.parse(Key::Char('j')), Some(PeepEvent::MoveDown(2)));
+
+        assert_eq!(kb.parse(Key::Char('1')), None);
+        assert_eq!(kb.parse(Key::Char('0')), None);
+        assert_eq!(kb.parse(Key::Char('h')), Some(PeepEvent::MoveLeft(10)));
+
+        assert_eq!(kb.parse(Key::Char('1')), None);
+        assert_eq!(kb.parse(Key::Char('0')), None);
+        assert_eq!(kb.parse(Key::Char('=')), Some(PeepEvent::SetNumOfLines(10)));
+
+        for c in "100000".chars() {
+            assert_eq!(kb.parse(Key::Char(c)), None);
+        }
+        assert_eq!(
+            kb.parse(Key::Char('g')),
+            Some(PeepEvent::MoveToLineNumber(99_999))
+        );
 
-        assert_eq!(kb.parse('1'), None);
-        assert_eq!(kb.parse('2'), None);
-        assert_eq!(kb.parse('\x1b'), None);
+        // special keys take the count
+        assert_eq!(kb.parse(Key::Char('5')), None);
+        assert_eq!(kb.parse(Key::Down), Some(PeepEvent::MoveDown(5)));
+        assert_eq!(kb.parse(Key::PageDown), Some(PeepEvent::MoveDownPages(1)));
+        assert_eq!(kb.parse(Key::Home), Some(PeepEvent::MoveToTopOfLines));
+    }
+
+    #[test]
+    fn test_keybind_history() {
+        let mut kb = default::KeyBind::new();
+        let history = Arc::new(Mutex::new(History::new(HISTORY_SIZE)));
+        history.lock().unwrap().push("old");
+        kb.set_history(history.clone());
+
+        let search = |s: &str| Some(PeepEvent::SearchIncremental(s.to_owned()));
+        assert_eq!(kb.parse(Key::Char('/')), search(""));
+        assert_eq!(kb.parse(Key::Char('b')), search("b"));
+        // the pattern is added by the app only if it is accepted
+        assert_eq!(kb.parse(Key::Char('\n')), Some(PeepEvent::SearchTrigger));
+        assert_eq!(history.lock().unwrap().len(), 1);
+
+        assert_eq!(kb.parse(Key::Char('/')), search(""));
+        assert_eq!(kb.parse(Key::Char('a')), search("a"));
+        assert_eq!(kb.parse(Key::Char('\n')), Some(PeepEvent::SearchTrigger));
+        history.lock().unwrap().push("a");
+
+        assert_eq!(kb.parse(Key::Char('/')), search(""));
+        assert_eq!(kb.parse(Key::Char('x')), search("x"));
+        assert_eq!(kb.parse(Key::Up), search("a"));
+        assert_eq!(kb.parse(Key::Up), search("old"));
+        assert_eq!(kb.parse(Key::Up), search("old"));
+        assert_eq!(kb.parse(Key::Down), search("a"));
+        // the typed pattern is restored
+        assert_eq!(kb.parse(Key::Down), search("x"));
+        assert_eq!(kb.parse(Key::Down), None);
+
+        // recalled pattern is edited and moved to the newest
+        assert_eq!(kb.parse(Key::Up), search("a"));
+        assert_eq!(kb.parse(Key::Up), search("old"));
+        assert_eq!(kb.parse(Key::Char('\x08')), search("ol"));
+        assert_eq!(kb.parse(Key::Char('d')), search("old"));
+        assert_eq!(kb.parse(Key::Char('\n')), Some(PeepEvent::SearchTrigger));
+        history.lock().unwrap().push("old");
+        assert_eq!(kb.parse(Key::Char('/')), search(""));
+        assert_eq!(kb.parse(Key::Up), search("old"));
+        assert_eq!(kb.parse(Key::Up), search("a"));
+        assert_eq!(kb.parse(Key::Char('\x1b')), Some(PeepEvent::Cancel));
+
+        // Up is a command out of search
+        assert_eq!(kb.parse(Key::Up), Some(PeepEvent::MoveUp(1)));
+    }
+
+    #[test]
+    fn test_keybind_filter() {
+        let mut kb = default::KeyBind::new();
+
+        assert_eq!(
+            kb.parse(Key::Char('&')),
+            Some(PeepEvent::FilterIncremental("".to_owned()))
+        );
+        assert_eq!(
+            kb.parse(Key::Char('!')),
+            Some(PeepEvent::FilterIncremental("!".to_owned()))
+        );
+        assert_eq!(
+            kb.parse(Key::Char('j')),
+            Some(PeepEvent::FilterIncremental("!j".to_owned()))
+        );
+        assert_eq!(
+            kb.parse(Key::Char('\n')),
+            Some(PeepEvent::FilterTrigger("!j".to_owned()))
+        );
+        assert_eq!(kb.parse(Key::Char('j')), Some(PeepEvent::MoveDown(1)));
+
+        // empty filter
+        assert_eq!(
+            kb.parse(Key::Char('&')),
+            Some(PeepEvent::FilterIncremental("".to_owned()))
+        );
+        assert_eq!(
+            kb.parse(Key::Char('\n')),
+            Some(PeepEvent::FilterTrigger("".to_owned()))
+        );
+
+        // cancel
+        assert_eq!(
+            kb.parse(Key::Char('&')),
+            Some(PeepEvent::FilterIncremental("".to_owned()))
+        );
+        assert_eq!(
+            kb.parse(Key::Char('a')),
+            Some(PeepEvent::FilterIncremental("a".to_owned()))
+        );
+        assert_eq!(kb.parse(Key::Char('\x1b')), Some(PeepEvent::Cancel));
+        assert_eq!(kb.parse(Key::Char('j')), Some(PeepEvent::MoveDown(1)));
+    }
+
+    #[test]
+    fn test_keybind_highlight() {
+        let mut kb = default::KeyBind::new();
+
+        assert_eq!(kb.parse(Key::Char(':')), None);
+        assert_eq!(
+            kb.parse(Key::Char('h')),
+            Some(PeepEvent::HighlightIncremental("".to_owned()))
+        );
+        for c in "E=re".chars() {
+            kb.parse(Key::Char(c));
+        }
+        assert_eq!(
+            kb.parse(Key::Char('d')),
+            Some(PeepEvent::HighlightIncremental("E=red".to_owned()))
+        );
+        assert_eq!(
+            kb.parse(Key::Char('\n')),
+            Some(PeepEvent::HighlightTrigger("E=red".to_owned()))
+        );
+        assert_eq!(kb.parse(Key::Char('j')), Some(PeepEvent::MoveDown(1)));
+
+        // the count is dropped
+        assert_eq!(kb.parse(Key::Char('3')), None);
+        assert_eq!(kb.parse(Key::Char(':')), None);
+        assert_eq!(
+            kb.parse(Key::Char('h')),
+            Some(PeepEvent::HighlightIncremental("".to_owned()))
+        );
+        assert_eq!(kb.parse(Key::Char('\x1b')), Some(PeepEvent::Cancel));
+        assert_eq!(kb.parse(Key::Char('j')), Some(PeepEvent::MoveDown(1)));
+    }
+
+    #[test]
+    fn test_keybind_offset() {
+        let mut kb = default::KeyBind::new();
+
+        assert_eq!(kb.parse(Key::Char(':')), None);
+        assert_eq!(kb.parse(Key::Char('x')), Some(PeepEvent::ToggleHexView));
+        assert_eq!(kb.parse(Key::Char(':')), None);
+        assert_eq!(
+            kb.parse(Key::Char('o')),
+            Some(PeepEvent::GotoOffsetIncremental("".to_owned()))
+        );
+        assert_eq!(
+            kb.parse(Key::Char('0')),
+            Some(PeepEvent::GotoOffsetIncremental("0".to_owned()))
+        );
+        kb.parse(Key::Char('x'));
+        assert_eq!(
+            kb.parse(Key::Char('f')),
+            Some(PeepEvent::GotoOffsetIncremental("0xf".to_owned()))
+        );
+        assert_eq!(
+            kb.parse(Key::Char('\n')),
+            Some(PeepEvent::GotoOffsetTrigger("0xf".to_owned()))
+        );
+        assert_eq!(kb.parse(Key::Char('j')), Some(PeepEvent::MoveDown(1)));
+    }
 
-        assert_eq!(kb.parse('2'), None);
-        assert_eq!(kb.parse('j'), Some(PeepEvent::MoveDown(2)));
+    #[test]
+    fn test_keybind_mark() {
+        let mut kb = default::KeyBind::new();
 
-        assert_eq!(kb.parse('1'), None);
-        assert_eq!(kb.parse('0'), None);
-        assert_eq!(kb.parse('h'), Some(PeepEvent::MoveLeft(10)));
+        assert_eq!(kb.parse(Key::Char('m')), None);
+        assert_eq!(kb.parse(Key::Char('a')), Some(PeepEvent::SetMark('a')));
+        assert_eq!(kb.parse(Key::Char('\'')), None);
+        assert_eq!(kb.parse(Key::Char('a')), Some(PeepEvent::JumpToMark('a')));
+        assert_eq!(kb.parse(Key::Char('\'')), None);
+        assert_eq!(kb.parse(Key::Char('\'')), Some(PeepEvent::JumpToMark('\'')));
 
-        assert_eq!(kb.parse('1'), None);
-        assert_eq!(kb.parse('0'), None);
-        assert_eq!(kb.parse('='), Some(PeepEvent::SetNumOfLines(10)));
+        // the command keys are taken as the name
+        assert_eq!(kb.parse(Key::Char('m')), None);
+        assert_eq!(kb.parse(Key::Char('j')), Some(PeepEvent::SetMark('j')));
+
+        // cancel
+        assert_eq!(kb.parse(Key::Char('\'')), None);
+        assert_eq!(kb.parse(Key::Char('\x1b')), Some(PeepEvent::Cancel));
+        assert_eq!(kb.parse(Key::Char('m')), None);
+        assert_eq!(kb.parse(Key::Down), Some(PeepEvent::Message(None)));
+        assert_eq!(kb.parse(Key::Char('j')), Some(PeepEvent::MoveDown(1)));
     }
 
     #[test]
@@ -370,63 +825,111 @@ mod tests {
 
         // search commands
         assert_eq!(
-            kb.parse('/'),
+            kb.parse(Key::Char('/')),
             Some(PeepEvent::SearchIncremental("".to_owned()))
         );
         assert_eq!(
-            kb.parse('w'),
+            kb.parse(Key::Char('w')),
             Some(PeepEvent::SearchIncremental("w".to_owned()))
         );
         assert_eq!(
-            kb.parse('o'),
+            kb.parse(Key::Char('o')),
             Some(PeepEvent::SearchIncremental("wo".to_owned()))
         );
         assert_eq!(
-            kb.parse('r'),
+            kb.parse(Key::Char('r')),
             Some(PeepEvent::SearchIncremental("wor".to_owned()))
         );
         assert_eq!(
-            kb.parse('d'),
+            kb.parse(Key::Char('d')),
             Some(PeepEvent::SearchIncremental("word".to_owned()))
         );
-        assert_eq!(kb.parse('\n'), Some(PeepEvent::SearchTrigger));
+        assert_eq!(kb.parse(Key::Char('\n')), Some(PeepEvent::SearchTrigger));
 
         assert_eq!(
-            kb.parse('/'),
+            kb.parse(Key::Char('/')),
             Some(PeepEvent::SearchIncremental("".to_owned()))
         );
         assert_eq!(
-            kb.parse('a'),
+            kb.parse(Key::Char('a')),
             Some(PeepEvent::SearchIncremental("a".to_owned()))
         );
         assert_eq!(
-            kb.parse('b'),
+            kb.parse(Key::Char('b')),
             Some(PeepEvent::SearchIncremental("ab".to_owned()))
         );
         assert_eq!(
-            kb.parse('\x08'),
+            kb.parse(Key::Char('\x08')),
             Some(PeepEvent::SearchIncremental("a".to_owned()))
         );
         assert_eq!(
-            kb.parse('\x08'),
+            kb.parse(Key::Char('\x08')),
             Some(PeepEvent::SearchIncremental("".to_owned()))
         );
-        assert_eq!(kb.parse('\x08'), Some(PeepEvent::Cancel));
+        assert_eq!(kb.parse(Key::Char('\x08')), Some(PeepEvent::Cancel));
 
         assert_eq!(
-            kb.parse('/'),
+            kb.parse(Key::Char('/')),
             Some(PeepEvent::SearchIncremental("".to_owned()))
         );
         assert_eq!(
-            kb.parse('w'),
+            kb.parse(Key::Char('w')),
             Some(PeepEvent::SearchIncremental("w".to_owned()))
         );
         assert_eq!(
-            kb.parse('o'),
+            kb.parse(Key::Char('o')),
             Some(PeepEvent::SearchIncremental("wo".to_owned()))
         );
-        assert_eq!(kb.parse('\n'), Some(PeepEvent::SearchTrigger));
-        assert_eq!(kb.parse('n'), Some(PeepEvent::SearchNext));
-        assert_eq!(kb.parse('N'), Some(PeepEvent::SearchPrev));
+        assert_eq!(kb.parse(Key::Char('\n')), Some(PeepEvent::SearchTrigger));
+        assert_eq!(kb.parse(Key::Char('n')), Some(PeepEvent::SearchNext));
+        assert_eq!(kb.parse(Key::Char('N')), Some(PeepEvent::SearchPrev));
+
+        // wide chars are removed one by one
+        assert_eq!(
+            kb.parse(Key::Char('/')),
+            Some(PeepEvent::SearchIncremental("".to_owned()))
+        );
+        for (c, p) in [('検', "検"), ('索', "検索"), ('é', "検索é")].iter() {
+            assert_eq!(
+                kb.parse(Key::Char(*c)),
+                Some(PeepEvent::SearchIncremental((*p).to_owned()))
+            );
+        }
+        assert_eq!(
+            kb.parse(Key::Char('\x7f')),
+            Some(PeepEvent::SearchIncremental("検索".to_owned()))
+        );
+        assert_eq!(
+            kb.parse(Key::Char('\x08')),
+            Some(PeepEvent::SearchIncremental("検".to_owned()))
+        );
+    }
+
+    #[test]
+    fn test_keybind_command_table() {
+        use std::collections::HashMap;
+
+        let cmap: HashMap<Vec<Key>, PeepEvent> = [
+            (vec![Key::Char('\x16')], PeepEvent::MoveDownPages(1)),
+            (
+                vec![Key::Char('g'), Key::Char('g')],
+                PeepEvent::MoveToTopOfLines,
+            ),
+        ]
+        .iter()
+        .cloned()
+        .collect();
+        let mut kb = default::KeyBind::with_command_table(cmap);
+
+        // Ctr-v is available because it is in the table
+        assert_eq!(
+            kb.parse(Key::Char('\x16')),
+            Some(PeepEvent::MoveDownPages(1))
+        );
+        assert_eq!(kb.parse(Key::Char('g')), None);
+        assert_eq!(kb.parse(Key::Char('g')), Some(PeepEvent::MoveToTopOfLines));
+        // default keys are not bound
+        assert_eq!(kb.parse(Key::Char('j')), Some(PeepEvent::Message(None)));
+        assert_eq!(kb.parse(Key::Char('\x0e')), None);
     }
 }
@@ -1,11 +1,23 @@
+pub mod ansi;
 pub mod app;
+pub mod config;
 pub mod csi;
 pub mod event;
 pub mod filewatch;
+pub mod filter;
+pub mod hexdump;
+pub mod highlight;
+pub mod history;
+pub mod input;
 pub mod keybind;
+pub mod linesource;
+pub mod log;
 pub mod logger;
+pub mod matchcount;
 pub mod pane;
+pub mod printable;
 pub mod search;
+pub mod syntax;
 pub mod tab;
 pub mod term;
 pub mod unicode_divide;
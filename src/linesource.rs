@@ -0,0 +1,731 @@
+//! linesource module
+
+use crate::printable;
+use std::cmp;
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek};
+use std::ops;
+use std::os::unix::fs::{FileExt, MetadataExt};
+use std::process;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, spawn};
+use std::time::Duration;
+use unicode_width::UnicodeWidthStr;
+
+/// What happened to the source on update.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SourceChange {
+    /// Lines were appended or nothing changed.
+    Appended,
+    /// The source was truncated, and it was read again from the head.
+    Truncated,
+    /// The source was replaced by a new one (e.g. log rotation), and it was reopened.
+    Reopened,
+}
+
+/// Line oriented text source that Pane draws from.
+pub trait LineSource {
+    /// Return the number of lines available now.
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Return the lines of the range. The range is clamped to the available lines.
+    /// No lines are returned if they can't be read, e.g. the file was truncated before the update.
+    fn lines(&self, range: ops::Range<usize>) -> Vec<String>;
+
+    /// Return the line of the index.
+    fn line(&self, n: usize) -> Option<String> {
+        self.lines(n..n + 1).pop()
+    }
+
+    /// Pick up the data appended to the source since the last call.
+    fn update(&mut self) -> io::Result<SourceChange> {
+        Ok(SourceChange::Appended)
+    }
+
+    /// Block until the whole source is available.
+    fn sync(&self) {}
+
+    /// Return whether the source is still being loaded and more lines will be available.
+    fn is_loading(&self) -> bool {
+        false
+    }
+
+    /// Return the index of the origin and the tag of the line
+    /// if the source is merged from several sources.
+    fn tag(&self, _n: usize) -> Option<(usize, String)> {
+        None
+    }
+
+    /// Return the max display width of tags.
+    fn tag_width(&self) -> usize {
+        0
+    }
+
+    /// Return the line number of the original source
+    /// if the lines are picked out from another source.
+    fn origin_line(&self, n: usize) -> usize {
+        n
+    }
+
+    /// Return the number of bytes of the raw data available now,
+    /// or None if the source keeps only the decoded lines.
+    fn byte_len(&self) -> Option<u64> {
+        None
+    }
+
+    /// Return the raw bytes of the range, or None if the source keeps only the decoded lines.
+    /// The range is clamped to the available bytes.
+    fn read_bytes(&self, _range: ops::Range<u64>) -> Option<Vec<u8>> {
+        None
+    }
+
+    /// Return the offset of the head of the line in the raw data,
+    /// or None if the source keeps only the decoded lines.
+    fn offset_of_line(&self, _n: usize) -> Option<u64> {
+        None
+    }
+
+    /// Return the line that has the byte at the offset of the raw data,
+    /// or None if the source keeps only the decoded lines.
+    fn line_at_offset(&self, _offset: u64) -> Option<usize> {
+        None
+    }
+}
+
+impl LineSource for Vec<String> {
+    fn len(&self) -> usize {
+        Vec::len(self)
+    }
+
+    fn lines(&self, range: ops::Range<usize>) -> Vec<String> {
+        let end = cmp::min(range.end, Vec::len(self));
+        let start = cmp::min(range.start, end);
+        self[start..end].to_vec()
+    }
+}
+
+/// Line source that merges lines of several sources in arrival order.
+/// Each line is tagged with its origin.
+pub struct MergedSource {
+    tags: Vec<String>,
+    lines: Vec<(usize, String)>,
+}
+
+impl MergedSource {
+    /// tags are the names of origins.
+    pub fn new(tags: Vec<String>) -> Self {
+        Self {
+            tags,
+            lines: Vec::new(),
+        }
+    }
+
+    /// Append the line that comes from the origin.
+    pub fn push(&mut self, origin: usize, line: String) {
+        self.lines.push((origin, line));
+    }
+}
+
+impl LineSource for MergedSource {
+    fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    fn lines(&self, range: ops::Range<usize>) -> Vec<String> {
+        let end = cmp::min(range.end, self.lines.len());
+        let start = cmp::min(range.start, end);
+        self.lines[start..end]
+            .iter()
+            .map(|(_, l)| l.to_owned())
+            .collect()
+    }
+
+    fn tag(&self, n: usize) -> Option<(usize, String)> {
+        self.lines
+            .get(n)
+            .map(|&(origin, _)| (origin, self.tags[origin].to_owned()))
+    }
+
+    fn tag_width(&self) -> usize {
+        self.tags
+            .iter()
+            .map(|t| UnicodeWidthStr::width(t.as_str()))
+            .fold(0, cmp::max)
+    }
+}
+
+/// Offsets of line heads in the file.
+struct LineIndex {
+    /// Start offset of every line. The last one is the head of the line being read.
+    heads: Vec<u64>,
+    /// Number of bytes already scanned.
+    scanned: u64,
+}
+
+impl LineIndex {
+    fn new() -> Self {
+        Self {
+            heads: vec![0],
+            scanned: 0,
+        }
+    }
+
+    fn len(&self) -> usize {
+        if *self.heads.last().unwrap() < self.scanned {
+            // the last line is not terminated yet
+            self.heads.len()
+        } else {
+            self.heads.len() - 1
+        }
+    }
+
+    /// Return the byte range of the line without its line terminator.
+    fn range_of(&self, n: usize) -> ops::Range<u64> {
+        let start = self.heads[n];
+        let end = if n + 1 < self.heads.len() {
+            self.heads[n + 1] - 1
+        } else {
+            self.scanned
+        };
+        start..end
+    }
+
+    fn push_chunk(&mut self, chunk: &[u8]) {
+        let base = self.scanned;
+        for (i, _) in chunk.iter().enumerate().filter(|(_, &b)| b == b'\n') {
+            self.heads.push(base + i as u64 + 1);
+        }
+        self.scanned += chunk.len() as u64;
+    }
+}
+
+/// Number of bytes that are read at once.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Scan the file from the index position to EOF.
+fn build_index(file: &File, index: &Mutex<LineIndex>) -> io::Result<()> {
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    loop {
+        let pos = index.lock().unwrap().scanned;
+        let n = file.read_at(&mut buf, pos)?;
+        if n == 0 {
+            break;
+        }
+        index.lock().unwrap().push_chunk(&buf[..n]);
+    }
+    Ok(())
+}
+
+/// Compression format of a file
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+    Xz,
+    Bzip2,
+}
+
+impl Compression {
+    /// Detect the format by the magic bytes at the head of the file.
+    pub fn detect(head: &[u8]) -> Option<Self> {
+        if head.starts_with(&[0x1f, 0x8b]) {
+            Some(Compression::Gzip)
+        } else if head.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(Compression::Zstd)
+        } else if head.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Some(Compression::Xz)
+        } else if head.starts_with(b"BZh") {
+            Some(Compression::Bzip2)
+        } else {
+            None
+        }
+    }
+
+    /// Return the reader of the decompressed data.
+    /// Concatenated streams are decoded as one.
+    fn decoder<'a, R: Read + 'a>(&self, reader: R) -> io::Result<Box<dyn Read + 'a>> {
+        Ok(match self {
+            Compression::Gzip => Box::new(flate2::read::MultiGzDecoder::new(reader)),
+            Compression::Zstd => Box::new(zstd::stream::read::Decoder::new(reader)?),
+            Compression::Xz => Box::new(xz2::read::XzDecoder::new_multi_decoder(reader)),
+            Compression::Bzip2 => Box::new(bzip2::read::MultiBzDecoder::new(reader)),
+        })
+    }
+}
+
+/// Create the temporary file that is unlinked at once,
+/// so that it's deleted when it's closed, even if peep is killed.
+fn unlinked_temp_file() -> io::Result<File> {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    loop {
+        let n = COUNT.fetch_add(1, Ordering::Relaxed);
+        let path = env::temp_dir().join(format!("peep-{}-{}", process::id(), n));
+        match OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)
+        {
+            Ok(file) => {
+                fs::remove_file(&path)?;
+                return Ok(file);
+            }
+            // left by the killed process of the same pid
+            Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Data of the file that the lines are read from.
+#[derive(Clone)]
+enum Content {
+    /// The lines are read from the file on demand.
+    Plain(Arc<File>),
+    /// The file is decompressed into the temporary file, because it can't be read at random offsets.
+    /// The decompressed data is kept on the disk of the temporary directory instead of memory.
+    Compressed {
+        file: Arc<File>,
+        compression: Compression,
+        decoded: Arc<File>,
+    },
+}
+
+impl Content {
+    fn open(path: &str) -> io::Result<Self> {
+        Self::detect(Arc::new(File::open(path)?))
+    }
+
+    /// Detect the compression of the file.
+    fn detect(file: Arc<File>) -> io::Result<Self> {
+        let mut head = [0u8; 6];
+        let n = file.read_at(&mut head, 0)?;
+        Ok(match Compression::detect(&head[..n]) {
+            Some(compression) => Content::Compressed {
+                file,
+                compression,
+                decoded: Arc::new(unlinked_temp_file()?),
+            },
+            None => Content::Plain(file),
+        })
+    }
+
+    fn file(&self) -> &Arc<File> {
+        match self {
+            Content::Plain(file) | Content::Compressed { file, .. } => file,
+        }
+    }
+
+    fn compression(&self) -> Option<Compression> {
+        match self {
+            Content::Plain(_) => None,
+            Content::Compressed { compression, .. } => Some(*compression),
+        }
+    }
+
+    /// Index the lines from the index position.
+    /// A compressed file is decoded from the head only when the index is empty,
+    /// because the decoder can't resume from the middle of the stream.
+    fn build_index(&self, index: &Mutex<LineIndex>) -> io::Result<()> {
+        let (file, compression, decoded) = match self {
+            Content::Plain(file) => return build_index(file, index),
+            Content::Compressed {
+                file,
+                compression,
+                decoded,
+            } => (file, compression, decoded),
+        };
+        if index.lock().unwrap().scanned > 0 {
+            return Ok(());
+        }
+        decoded.set_len(0)?;
+
+        let mut reader: &File = file;
+        reader.seek(io::SeekFrom::Start(0))?;
+        let mut decoder = compression.decoder(io::BufReader::new(reader))?;
+        let mut buf = vec![0u8; CHUNK_SIZE];
+        loop {
+            let n = match decoder.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                // show the data decoded before the broken part, like `zcat`
+                Err(_) => break,
+            };
+            let pos = index.lock().unwrap().scanned;
+            decoded.write_all_at(&buf[..n], pos)?;
+            index.lock().unwrap().push_chunk(&buf[..n]);
+        }
+        Ok(())
+    }
+
+    fn read_range(&self, range: ops::Range<u64>) -> io::Result<Vec<u8>> {
+        let file = match self {
+            Content::Plain(file) => file,
+            Content::Compressed { decoded, .. } => decoded,
+        };
+        let mut buf = vec![0u8; (range.end - range.start) as usize];
+        file.read_exact_at(&mut buf, range.start)?;
+        Ok(buf)
+    }
+}
+
+/// Chomp the end of CR.
+/// Invalid UTF-8 bytes are kept to be shown as `<XX>`.
+fn decode_line(raw: &[u8]) -> String {
+    let raw = raw.strip_suffix(b"\r").unwrap_or(raw);
+    printable::decode_lossy(raw)
+}
+
+/// Line source backed by a file.
+///
+/// The line index is built on a background thread, and only the requested lines are read.
+/// A compressed file is decompressed into the temporary file on the thread,
+/// and it is decompressed again from the head when it grows.
+pub struct FileSource {
+    path: String,
+    content: Content,
+    // size of the file when it was opened or decoded
+    file_len: u64,
+    index: Arc<Mutex<LineIndex>>,
+    indexing: Arc<AtomicBool>,
+}
+
+impl FileSource {
+    pub fn open(path: &str) -> io::Result<Self> {
+        let content = Content::open(path)?;
+        let file_len = content.file().metadata()?.len();
+        let index = Arc::new(Mutex::new(LineIndex::new()));
+        let indexing = Arc::new(AtomicBool::new(true));
+
+        let (c, idx, busy) = (content.clone(), index.clone(), indexing.clone());
+        spawn(move || {
+            let _ = c.build_index(&idx);
+            busy.store(false, Ordering::Release);
+        });
+
+        Ok(Self {
+            path: path.to_owned(),
+            content,
+            file_len,
+            index,
+            indexing,
+        })
+    }
+
+    /// Return the compression format of the file, or None if it is not compressed.
+    pub fn compression(&self) -> Option<Compression> {
+        self.content.compression()
+    }
+
+    /// Return whether the path points another file than the opened one.
+    fn is_replaced(&self) -> bool {
+        match (fs::metadata(&self.path), self.content.file().metadata()) {
+            (Ok(new), Ok(cur)) => new.dev() != cur.dev() || new.ino() != cur.ino(),
+            _ => false,
+        }
+    }
+
+    /// Return whether the file became shorter than the indexed size.
+    fn is_truncated(&self) -> io::Result<bool> {
+        let len = self.content.file().metadata()?.len();
+        Ok(match self.content {
+            Content::Plain(_) => len < self.index.lock().unwrap().scanned,
+            Content::Compressed { .. } => len < self.file_len,
+        })
+    }
+
+    /// Return whether the compressed file grew, so that it has to be decoded again.
+    fn is_recompressed(&self) -> io::Result<bool> {
+        Ok(self.compression().is_some() && self.content.file().metadata()?.len() > self.file_len)
+    }
+
+    /// Check truncation and replacement of the file.
+    /// The index is reset if either of them happened, or if the compressed file grew.
+    fn detect_change(&mut self) -> io::Result<SourceChange> {
+        let change = if self.is_replaced() {
+            match Content::open(&self.path) {
+                Ok(content) => {
+                    self.content = content;
+                    SourceChange::Reopened
+                }
+                // keep reading the old file until the new one appears
+                Err(_) => SourceChange::Appended,
+            }
+        } else if self.is_truncated()? {
+            SourceChange::Truncated
+        } else {
+            SourceChange::Appended
+        };
+        if change != SourceChange::Appended || self.is_recompressed()? {
+            // the file may be rewritten in another format
+            self.content = Content::detect(self.content.file().clone())?;
+            *self.index.lock().unwrap() = LineIndex::new();
+            self.file_len = self.content.file().metadata()?.len();
+        }
+        Ok(change)
+    }
+
+    /// Return whether the background indexing is running or not.
+    pub fn is_indexing(&self) -> bool {
+        self.indexing.load(Ordering::Acquire)
+    }
+
+    /// Wait until at least n lines are indexed or the indexing is finished.
+    pub fn wait_for_lines(&self, n: usize) {
+        while self.is_indexing() && self.len() < n {
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+}
+
+impl LineSource for FileSource {
+    fn len(&self) -> usize {
+        self.index.lock().unwrap().len()
+    }
+
+    fn lines(&self, range: ops::Range<usize>) -> Vec<String> {
+        let ranges: Vec<ops::Range<u64>> = {
+            let index = self.index.lock().unwrap();
+            let end = cmp::min(range.end, index.len());
+            let start = cmp::min(range.start, end);
+            (start..end).map(|n| index.range_of(n)).collect()
+        };
+        let (head, tail) = match (ranges.first(), ranges.last()) {
+            (Some(h), Some(t)) => (h.start, t.end),
+            _ => return Vec::new(),
+        };
+        match self.content.read_range(head..tail) {
+            Ok(bytes) => ranges
+                .iter()
+                .map(|r| decode_line(&bytes[(r.start - head) as usize..(r.end - head) as usize]))
+                .collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    fn update(&mut self) -> io::Result<SourceChange> {
+        self.sync();
+        let change = self.detect_change()?;
+        self.content.build_index(&self.index)?;
+        Ok(change)
+    }
+
+    fn sync(&self) {
+        self.wait_for_lines(usize::MAX);
+    }
+
+    fn is_loading(&self) -> bool {
+        self.is_indexing()
+    }
+
+    fn byte_len(&self) -> Option<u64> {
+        Some(self.index.lock().unwrap().scanned)
+    }
+
+    fn read_bytes(&self, range: ops::Range<u64>) -> Option<Vec<u8>> {
+        let end = cmp::min(range.end, self.index.lock().unwrap().scanned);
+        let start = cmp::min(range.start, end);
+        self.content.read_range(start..end).ok()
+    }
+
+    fn offset_of_line(&self, n: usize) -> Option<u64> {
+        self.index.lock().unwrap().heads.get(n).cloned()
+    }
+
+    fn line_at_offset(&self, offset: u64) -> Option<usize> {
+        let index = self.index.lock().unwrap();
+        Some(
+            index
+                .heads
+                .partition_point(|&h| h <= offset)
+                .saturating_sub(1),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs::{self, OpenOptions};
+    use std::io::Write;
+
+    fn temp_path(name: &str) -> String {
+        let mut p = env::temp_dir();
+        p.push(format!("peep-linesource-{}-{}", name, std::process::id()));
+        p.to_str().unwrap().to_owned()
+    }
+
+    #[test]
+    fn test_vec_source() {
+        let v: Vec<String> = vec!["a".to_owned(), "b".to_owned(), "c".to_owned()];
+        assert_eq!(LineSource::len(&v), 3);
+        assert_eq!(v.lines(1..3), vec!["b".to_owned(), "c".to_owned()]);
+        assert_eq!(v.lines(2..10), vec!["c".to_owned()]);
+        assert!(v.lines(5..10).is_empty());
+        assert_eq!(v.line(0), Some("a".to_owned()));
+        assert_eq!(v.line(3), None);
+    }
+
+    #[test]
+    fn test_merged_source() {
+        let mut src = MergedSource::new(vec!["a.log".to_owned(), "long.log".to_owned()]);
+        assert!(src.is_empty());
+        src.push(1, "x".to_owned());
+        src.push(0, "y".to_owned());
+        assert_eq!(src.len(), 2);
+        assert_eq!(src.lines(0..5), vec!["x".to_owned(), "y".to_owned()]);
+        assert_eq!(src.tag(0), Some((1, "long.log".to_owned())));
+        assert_eq!(src.tag(1), Some((0, "a.log".to_owned())));
+        assert_eq!(src.tag(2), None);
+        assert_eq!(src.tag_width(), 8);
+    }
+
+    #[test]
+    fn test_file_source() {
+        let path = temp_path("file");
+        fs::write(&path, "abc\r\n\nあいう\nxyz").unwrap();
+
+        let mut src = FileSource::open(&path).unwrap();
+        src.sync();
+        assert_eq!(src.len(), 4);
+        assert_eq!(
+            src.lines(0..4),
+            vec![
+                "abc".to_owned(),
+                "".to_owned(),
+                "あいう".to_owned(),
+                "xyz".to_owned()
+            ]
+        );
+        assert_eq!(src.line(2), Some("あいう".to_owned()));
+        assert!(src.lines(4..8).is_empty());
+        assert_eq!(src.byte_len(), Some(19));
+        assert_eq!(src.read_bytes(2..6), Some(b"c\r\n\n".to_vec()));
+        assert_eq!(src.read_bytes(16..30), Some(b"xyz".to_vec()));
+        assert_eq!(src.offset_of_line(2), Some(6));
+        assert_eq!(src.line_at_offset(5), Some(1));
+        assert_eq!(src.line_at_offset(15), Some(2));
+        assert_eq!(src.line_at_offset(16), Some(3));
+
+        // append data to the unterminated line
+        let mut f = OpenOptions::new().append(true).open(&path).unwrap();
+        write!(f, "123\nnew\n").unwrap();
+        src.update().unwrap();
+        assert_eq!(src.len(), 5);
+        assert_eq!(src.lines(3..5), vec!["xyz123".to_owned(), "new".to_owned()]);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    fn compress(compression: Compression, data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        match compression {
+            Compression::Gzip => {
+                let mut e = flate2::write::GzEncoder::new(&mut out, flate2::Compression::fast());
+                e.write_all(data).unwrap();
+                e.finish().unwrap();
+            }
+            Compression::Zstd => out = zstd::encode_all(data, 0).unwrap(),
+            Compression::Xz => {
+                let mut e = xz2::write::XzEncoder::new(&mut out, 1);
+                e.write_all(data).unwrap();
+                e.finish().unwrap();
+            }
+            Compression::Bzip2 => {
+                let mut e = bzip2::write::BzEncoder::new(&mut out, bzip2::Compression::fast());
+                e.write_all(data).unwrap();
+                e.finish().unwrap();
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn test_compressed_file_source() {
+        let path = temp_path("compressed");
+        for &compression in [
+            Compression::Gzip,
+            Compression::Zstd,
+            Compression::Xz,
+            Compression::Bzip2,
+        ]
+        .iter()
+        {
+            fs::write(
+                &path,
+                compress(compression, "abc\r\nあいう\nxyz".as_bytes()),
+            )
+            .unwrap();
+            let src = FileSource::open(&path).unwrap();
+            src.sync();
+            assert_eq!(src.compression(), Some(compression));
+            assert_eq!(src.len(), 3);
+            assert_eq!(src.line(1), Some("あいう".to_owned()));
+            assert_eq!(src.lines(0..10), vec!["abc", "あいう", "xyz"]);
+            assert_eq!(src.read_bytes(15..20), Some(b"xyz".to_vec()));
+        }
+
+        // gzip member appended like `gzip -c more >> file.gz`
+        let mut src = FileSource::open(&path).unwrap();
+        fs::write(&path, compress(Compression::Gzip, b"a\n")).unwrap();
+        assert_eq!(src.update().unwrap(), SourceChange::Truncated);
+        let mut f = OpenOptions::new().append(true).open(&path).unwrap();
+        f.write_all(&compress(Compression::Gzip, b"b\n")).unwrap();
+        assert_eq!(src.update().unwrap(), SourceChange::Appended);
+        assert_eq!(src.lines(0..10), vec!["a", "b"]);
+
+        // broken member after the first lines
+        let mut data = compress(Compression::Gzip, b"ok1\nok2\n");
+        data.extend_from_slice(b"\x1f\x8b\x08");
+        fs::write(&path, data).unwrap();
+        assert_eq!(src.update().unwrap(), SourceChange::Truncated);
+        assert_eq!(src.len(), 2);
+        assert_eq!(src.lines(0..10), vec!["ok1", "ok2"]);
+
+        // plain text is not decoded
+        fs::write(&path, "BZ\n").unwrap();
+        src = FileSource::open(&path).unwrap();
+        src.sync();
+        assert_eq!(src.compression(), None);
+        assert_eq!(src.lines(0..10), vec!["BZ"]);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_file_source_truncate_and_rotate() {
+        let path = temp_path("rotate");
+        let rotated = format!("{}.1", path);
+        fs::write(&path, "a\nb\nc\n").unwrap();
+
+        let mut src = FileSource::open(&path).unwrap();
+        src.sync();
+        assert_eq!(src.len(), 3);
+        assert_eq!(src.update().unwrap(), SourceChange::Appended);
+
+        // truncate like `> file`
+        fs::write(&path, "x\n").unwrap();
+        // the lines past the end can't be read until the update
+        assert!(src.lines(1..3).is_empty());
+        assert_eq!(src.update().unwrap(), SourceChange::Truncated);
+        assert_eq!(src.lines(0..10), vec!["x".to_owned()]);
+
+        // rename and create like logrotate
+        fs::rename(&path, &rotated).unwrap();
+        assert_eq!(src.update().unwrap(), SourceChange::Appended);
+        fs::write(&path, "new1\nnew2\n").unwrap();
+        assert_eq!(src.update().unwrap(), SourceChange::Reopened);
+        assert_eq!(src.lines(0..10), vec!["new1".to_owned(), "new2".to_owned()]);
+
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(&rotated).unwrap();
+    }
+}
@@ -0,0 +1,245 @@
+//! log module
+
+use crate::ansi::{self, Highlight};
+use regex::Regex;
+
+/// Style of timestamps (dim)
+const TIMESTAMP_SGR: &str = "\x1b[2m";
+
+/// Severity of a log line
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl Level {
+    /// Parse the level name of the common log formats, ignoring case.
+    pub fn parse(name: &str) -> Option<Self> {
+        Some(match name.to_ascii_lowercase().as_str() {
+            "trace" | "trc" => Level::Trace,
+            "debug" | "dbg" => Level::Debug,
+            "info" | "inf" | "information" | "notice" => Level::Info,
+            "warn" | "wrn" | "warning" => Level::Warn,
+            "error" | "err" | "fatal" | "crit" | "critical" | "alert" | "emerg" | "panic" => {
+                Level::Error
+            }
+            _ => return None,
+        })
+    }
+
+    /// Return the level of the syslog severity (0-7).
+    fn from_severity(n: u8) -> Self {
+        match n {
+            0..=3 => Level::Error,
+            4 => Level::Warn,
+            5 | 6 => Level::Info,
+            _ => Level::Debug,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Level::Trace => "TRACE",
+            Level::Debug => "DEBUG",
+            Level::Info => "INFO",
+            Level::Warn => "WARN",
+            Level::Error => "ERROR",
+        }
+    }
+
+    fn sgr(&self) -> &'static str {
+        match self {
+            Level::Trace => "\x1b[90m",
+            Level::Debug => "\x1b[34m",
+            Level::Info => "\x1b[32m",
+            Level::Warn => "\x1b[33m",
+            Level::Error => "\x1b[1;31m",
+        }
+    }
+
+    /// Return the next minimum level of the filter.
+    /// It goes DEBUG, INFO, WARN, ERROR and back to no filter.
+    pub fn cycle(level: Option<Self>) -> Option<Self> {
+        match level {
+            None | Some(Level::Trace) => Some(Level::Debug),
+            Some(Level::Debug) => Some(Level::Info),
+            Some(Level::Info) => Some(Level::Warn),
+            Some(Level::Warn) => Some(Level::Error),
+            Some(Level::Error) => None,
+        }
+    }
+}
+
+/// Level and timestamp found in a log line, with their byte ranges
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct LogLine {
+    pub level: Option<(Level, (usize, usize))>,
+    pub timestamp: Option<(usize, usize)>,
+}
+
+/// Parser of the log lines of env_logger, syslog, JSON lines and logfmt.
+pub struct LogParser {
+    // syslog priority like `<13>`
+    priority: Regex,
+    // patterns whose first group is the level name, in the order they are tried
+    levels: Vec<Regex>,
+    timestamp: Regex,
+}
+
+impl Default for LogParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LogParser {
+    pub fn new() -> Self {
+        const NAMES: &str = "trace|debug|info|notice|warn|warning|error|err|fatal|crit|critical";
+        let levels = [
+            // JSON lines
+            format!(r#""(?:level|lvl|severity)"\s*:\s*"((?i:{}))""#, NAMES),
+            // logfmt
+            format!(r#"(?:^|\s)(?:level|lvl|severity)="?((?i:{}))\b"#, NAMES),
+            // env_logger and most of the others
+            format!(r"\b({})\b", NAMES.to_ascii_uppercase()),
+            format!(r"\[((?i:{}))\]", NAMES),
+        ];
+        Self {
+            priority: Regex::new(r"^<(\d{1,3})>").unwrap(),
+            levels: levels.iter().map(|p| Regex::new(p).unwrap()).collect(),
+            timestamp: Regex::new(concat!(
+                // ISO 8601 and RFC 3339
+                r"\d{4}-\d{2}-\d{2}[T ]\d{2}:\d{2}:\d{2}(?:[.,]\d+)?(?:Z|[+-]\d{2}:?\d{2})?",
+                // syslog
+                r"|\b[A-Z][a-z]{2} [ \d]\d \d{2}:\d{2}:\d{2}\b",
+                r"|\b\d{2}:\d{2}:\d{2}(?:[.,]\d+)?\b",
+            ))
+            .unwrap(),
+        }
+    }
+
+    /// Find the level and the timestamp of the line.
+    pub fn parse(&self, text: &str) -> LogLine {
+        LogLine {
+            level: self.find_level(text),
+            timestamp: self.timestamp.find(text).map(|m| (m.start(), m.end())),
+        }
+    }
+
+    /// Return the level of the line, or None if the line has no level.
+    pub fn level_of(&self, text: &str) -> Option<Level> {
+        self.find_level(text).map(|(level, _)| level)
+    }
+
+    fn find_level(&self, text: &str) -> Option<(Level, (usize, usize))> {
+        if let Some(caps) = self.priority.captures(text) {
+            let m = caps.get(1).unwrap();
+            if let Ok(n) = m.as_str().parse::<u8>() {
+                return Some((Level::from_severity(n % 8), (m.start(), m.end())));
+            }
+        }
+        self.levels.iter().find_map(|re| {
+            let m = re.captures(text)?.get(1)?;
+            Level::parse(m.as_str()).map(|level| (level, (m.start(), m.end())))
+        })
+    }
+
+    /// Return the highlight ranges of the level and the timestamp of the line.
+    pub fn ranges(&self, text: &str) -> Vec<Highlight> {
+        let line = self.parse(text);
+        let mut layers = vec![];
+        if let Some((level, (start, end))) = line.level {
+            layers.push(vec![Highlight::new(start, end, level.sgr())]);
+        }
+        if let Some((start, end)) = line.timestamp {
+            layers.push(vec![Highlight::new(start, end, TIMESTAMP_SGR)]);
+        }
+        ansi::merge_highlights(layers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn level_word(parser: &LogParser, text: &str) -> Option<(Level, String)> {
+        parser
+            .parse(text)
+            .level
+            .map(|(level, (s, e))| (level, text[s..e].to_owned()))
+    }
+
+    #[test]
+    fn test_parse() {
+        let parser = LogParser::new();
+
+        // env_logger
+        let text = "[2024-05-01T10:20:30Z WARN  app::net] retry after ERROR";
+        assert_eq!(
+            level_word(&parser, text),
+            Some((Level::Warn, "WARN".to_owned()))
+        );
+        assert_eq!(parser.parse(text).timestamp, Some((1, 21)));
+
+        // syslog
+        let text = "<11>May  1 10:20:30 host app[42]: disk full";
+        assert_eq!(parser.level_of(text), Some(Level::Error));
+        assert_eq!(parser.parse(text).timestamp, Some((4, 19)));
+        assert_eq!(parser.level_of("May  1 10:20:30 host app: up"), None);
+
+        // JSON lines
+        let text = r#"{"ts":"2024-05-01 10:20:30.123","level":"debug","msg":"info"}"#;
+        assert_eq!(
+            level_word(&parser, text),
+            Some((Level::Debug, "debug".to_owned()))
+        );
+        assert_eq!(parser.parse(text).timestamp, Some((7, 30)));
+
+        // logfmt
+        let text = "time=10:20:30 level=warning msg=\"slow\"";
+        assert_eq!(
+            level_word(&parser, text),
+            Some((Level::Warn, "warning".to_owned()))
+        );
+        assert_eq!(parser.level_of("at=info [error] x"), Some(Level::Error));
+        assert_eq!(parser.level_of("information about errors"), None);
+    }
+
+    #[test]
+    fn test_ranges() {
+        let parser = LogParser::new();
+        assert_eq!(
+            parser.ranges("10:20:30 ERROR x"),
+            vec![
+                Highlight::new(0, 8, TIMESTAMP_SGR),
+                Highlight::new(9, 14, Level::Error.sgr()),
+            ]
+        );
+        assert!(parser.ranges("plain").is_empty());
+    }
+
+    #[test]
+    fn test_cycle() {
+        let mut level = None;
+        let mut seen = vec![];
+        for _ in 0..5 {
+            level = Level::cycle(level);
+            seen.push(level);
+        }
+        assert_eq!(
+            seen,
+            vec![
+                Some(Level::Debug),
+                Some(Level::Info),
+                Some(Level::Warn),
+                Some(Level::Error),
+                None
+            ]
+        );
+        assert!(Level::Warn > Level::Info);
+    }
+}
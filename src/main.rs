@@ -1,13 +1,15 @@
-use getopts::Options;
+use getopts::{Matches, Options};
 use std::env;
 use std::io;
 use std::process;
 
 use peep::app::App;
+use peep::config;
+use peep::search::CaseMode;
 
 fn print_usage(prog: &str, version: &str, opts: &Options) {
     let brief = format!(
-        "{p} {v}\n\nUsage: {p} [OPTION]... [FILE]",
+        "{p} {v}\n\nUsage: {p} [OPTION]... [FILE]...",
         p = prog,
         v = version
     );
@@ -15,23 +17,44 @@ fn print_usage(prog: &str, version: &str, opts: &Options) {
     println!(
         "Commands on Normal Mode:
     (num)j Ctr-j Ctr-n  Scroll down
+    (num)DOWN           Scroll down
     (num)k Ctr-k Ctr-p  Scroll up
+    (num)UP             Scroll up
     (num)d Ctr-d        Scroll down half page
     (num)u Ctr-u        Scroll up half page
     (num)f Ctr-f SPACE  Scroll down a page
+    (num)PAGEDOWN       Scroll down a page
     (num)b Ctr-b        Scroll up a page
-    (num)l              Scroll horizontally right
-    (num)h              Scroll horizontally left
+    (num)PAGEUP         Scroll up a page
+    (num)l RIGHT        Scroll horizontally right
+    (num)h LEFT         Scroll horizontally left
     (num)L              Scroll horizontally right half page
     (num)H              Scroll horizontally left half page
     0 Ctr-a             Go to the beggining of line
     $ Ctr-e             Go to the end of line
-    g                   Go to the beggining of file
-    G                   Go to the end of file
+    g HOME              Go to the beggining of file
+    G END               Go to the end of file
     [num]g [num]G       Go to line [num]
+    m<letter>           Mark the current position with <letter>
+    '<letter>           Go to the position marked with <letter>
+    ''                  Go to the position before the last jump
     /pattern            Search forward in the file for the regex pattern
-    n                   Search next
-    N                   Search previous
+    n                   Go to the next match
+    N                   Go to the previous match
+    &pattern            Display only lines that match the pattern
+    &!pattern           Display only lines that don't match the pattern
+    &                   Clear the filters
+    :h pattern[=color]  Highlight the pattern with the color
+    :h -pattern         Remove the highlight of the pattern
+    :h                  Clear the highlights
+    :l                  Cycle the minimum log level (DEBUG, INFO, WARN, ERROR, all)
+    :x                  Toggle the hex view
+    :o offset           Go to the offset (0x prefixed hex or decimal)
+    :i                  Toggle smart-case search
+    :I                  Toggle case-insensitive search
+    :F                  Toggle fixed string search
+    :n                  Examine the next file
+    :p                  Examine the previous file
     q Ctr-c             Quit
     Q                   Clear output and Quit
     (num)+              Increment screen height
@@ -44,6 +67,14 @@ fn print_usage(prog: &str, version: &str, opts: &Options) {
 
 Commands on Following Mode:
     /pattern            Highlight the regex pattern
+    &pattern            Display only lines that match the pattern
+    &!pattern           Display only lines that don't match the pattern
+    &                   Clear the filters
+    :h pattern[=color]  Highlight the pattern with the color
+    :h -pattern         Remove the highlight of the pattern
+    :h                  Clear the highlights
+    :l                  Cycle the minimum log level (DEBUG, INFO, WARN, ERROR, all)
+    :x                  Toggle the hex view
     q Ctr-c             Quit
     Q                   Clear output and Quit
     (num)+              Increment screen height
@@ -56,6 +87,20 @@ Commands on Following Mode:
     );
 }
 
+/// Long names of the flags that can be turned off by the `--no-` prefixed options,
+/// so that a flag set in the config file can be overridden.
+const NEGATABLE_FLAGS: [&str; 9] = [
+    "print-line-number",
+    "follow",
+    "wrap",
+    "raw-control-chars",
+    "smart-case",
+    "ignore-case",
+    "fixed-strings",
+    "log",
+    "mouse",
+];
+
 fn print_version(prog: &str, version: &str) {
     println!("{} {}", prog, version);
 }
@@ -72,13 +117,47 @@ fn run() -> io::Result<()> {
         .optflag("N", "print-line-number", "print line numbers")
         .optflag("f", "follow", "output appended data as the file grows")
         .optflag("w", "wrap", "wrap text line")
-        .optflag("h", "help", "show this usage")
+        .optflag(
+            "R",
+            "raw-control-chars",
+            "output ANSI color escape sequences in raw form",
+        )
+        .optflag(
+            "i",
+            "smart-case",
+            "ignore case in searches unless the pattern has uppercase letters",
+        )
+        .optflag("I", "ignore-case", "ignore case in searches")
+        .optflag("F", "fixed-strings", "search the pattern as a fixed string")
+        .optmulti(
+            "",
+            "hl",
+            "always highlight the pattern with the color",
+            "PATTERN[=COLOR]",
+        )
+        .optopt(
+            "",
+            "language",
+            "color the syntax of the language (auto, none, rust, json, yaml, toml, diff)",
+            "LANG",
+        )
+        .optflag(
+            "",
+            "log",
+            "color the levels and the timestamps of log lines",
+        )
+        .optflag("", "mouse", "enable mouse wheel scrolling and clicks");
+    for name in NEGATABLE_FLAGS.iter() {
+        opts.optflag("", &format!("no-{}", name), &format!("turn off --{}", name));
+    }
+    opts.optflag("h", "help", "show this usage")
         .optflag("v", "version", "show version");
 
     let matches = opts
         .parse(args)
         .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
 
+    // the usage and the version are shown even if the config file is broken
     if matches.opt_present("h") {
         print_usage(prog, version, &opts);
         return Ok(());
@@ -89,8 +168,24 @@ fn run() -> io::Result<()> {
         return Ok(());
     }
 
-    let file_path = if !matches.free.is_empty() {
-        matches.free[0].clone()
+    // startup defaults are layered beneath the command line options
+    let mut layers = vec![];
+    if let Some(path) = config::options_file() {
+        let args = config::read_options(&path)?;
+        layers.push(parse_default_options(
+            &opts,
+            &path.display().to_string(),
+            args,
+        )?);
+    }
+    layers.push(parse_default_options(
+        &opts,
+        "PEEP_OPTIONS",
+        config::env_options(),
+    )?);
+
+    let file_paths = if !matches.free.is_empty() {
+        matches.free.clone()
     } else {
         if termion::is_tty(&io::stdin()) {
             // not find file name and pipe input
@@ -99,24 +194,101 @@ fn run() -> io::Result<()> {
                 format!("missing filename (\"{} --help\" for help)", prog),
             ));
         }
-        "-".to_owned()
+        vec!["-".to_owned()]
     };
 
+    // validate the key bindings before the terminal is set up
+    let keymap = config::load_keys()?;
+
     let mut app: App = Default::default();
-    app.show_linenumber = matches.opt_present("N");
-    app.follow_mode = matches.opt_present("f");
-    app.wraps_line = matches.opt_present("w");
+    app.keymap = keymap;
+    for m in layers.iter().chain(Some(&matches)) {
+        apply_options(&mut app, m);
+    }
+
+    app.run(&file_paths)
+}
+
+/// Parse the startup defaults from the config file or the environment variable.
+fn parse_default_options(opts: &Options, source: &str, args: Vec<String>) -> io::Result<Matches> {
+    let matches = opts
+        .parse(args)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, format!("{}: {}", source, e)))?;
+    if !matches.free.is_empty() || matches.opt_present("h") || matches.opt_present("v") {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "{}: file names and --help/--version are not allowed",
+                source
+            ),
+        ));
+    }
+    Ok(matches)
+}
+
+/// Return the flag given in the options.
+/// Some(false) is returned for the `--no-` prefixed option, and None if neither is given,
+/// so that the flag of the lower layer is kept. The later one wins if both are given.
+fn flag(matches: &Matches, name: &str) -> Option<bool> {
+    let on = matches.opt_positions(name).into_iter().max();
+    let off = matches
+        .opt_positions(&format!("no-{}", name))
+        .into_iter()
+        .max();
+    match (on, off) {
+        (None, None) => None,
+        (on, off) => Some(on > off),
+    }
+}
+
+/// Apply the given options to the app.
+fn apply_options(app: &mut App, matches: &Matches) {
+    if let Some(on) = flag(matches, "print-line-number") {
+        app.show_linenumber = on;
+    }
+    if let Some(on) = flag(matches, "follow") {
+        app.follow_mode = on;
+    }
+    if let Some(on) = flag(matches, "wrap") {
+        app.wraps_line = on;
+    }
+    if let Some(on) = flag(matches, "raw-control-chars") {
+        app.raw_control_chars = on;
+    }
+    if let Some(on) = flag(matches, "log") {
+        app.log = on;
+    }
+    if let Some(on) = flag(matches, "mouse") {
+        app.mouse = on;
+    }
+    match flag(matches, "smart-case") {
+        Some(true) => app.case_mode = CaseMode::Smart,
+        Some(false) if app.case_mode == CaseMode::Smart => app.case_mode = CaseMode::Sensitive,
+        _ => {}
+    }
+    match flag(matches, "ignore-case") {
+        Some(true) => app.case_mode = CaseMode::Insensitive,
+        Some(false) if app.case_mode == CaseMode::Insensitive => {
+            app.case_mode = CaseMode::Sensitive
+        }
+        _ => {}
+    }
+    if let Some(on) = flag(matches, "fixed-strings") {
+        app.fixed_string = on;
+    }
+    app.highlights.extend(matches.opt_strs("hl"));
+    if let Some(language) = matches.opt_str("language") {
+        app.language = Some(language);
+    }
     if let Ok(Some(nlines)) = matches.opt_get::<u16>("n") {
         app.nlines = nlines;
     }
     if let Ok(Some(tab_width)) = matches.opt_get::<u16>("t") {
         app.tab_width = tab_width;
     }
-    if let Ok(Some(start_line)) = matches.opt_get::<u16>("s") {
+    if let Ok(Some(start_line)) = matches.opt_get::<usize>("s") {
         app.start_line = start_line;
     }
-
-    app.run(&file_path)
 }
 
 fn main() {
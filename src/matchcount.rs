@@ -0,0 +1,153 @@
+//! matchcount module
+
+use crate::ansi;
+use crate::linesource::LineSource;
+use crate::search::Search;
+use std::cmp;
+
+/// Number of lines that are scanned at once.
+const COUNT_CHUNK_LINES: usize = 1024;
+
+/// Counter of search matches in the line source.
+/// Lines are scanned a chunk at a time, so that the count of big sources doesn't block inputs.
+pub struct MatchCounter {
+    // prefix[n] is the number of matches in the lines before n
+    prefix: Vec<usize>,
+    // how the lines are shown, which the pattern is matched with
+    tab_width: usize,
+    raw_control_chars: bool,
+}
+
+impl MatchCounter {
+    /// The pattern is matched with the lines as shown on the pane with the tab width
+    /// and the raw control chars mode.
+    pub fn new(tab_width: usize, raw_control_chars: bool) -> Self {
+        Self {
+            prefix: vec![0],
+            tab_width,
+            raw_control_chars,
+        }
+    }
+
+    /// Forget the count to start over.
+    pub fn reset(&mut self) {
+        self.prefix.truncate(1);
+    }
+
+    /// Return the number of scanned lines.
+    pub fn scanned(&self) -> usize {
+        self.prefix.len() - 1
+    }
+
+    /// Return whether all lines of the source are scanned.
+    pub fn is_complete(&self, source: &dyn LineSource) -> bool {
+        !source.is_loading() && self.scanned() >= source.len()
+    }
+
+    /// Count the matches of a chunk of lines that are not scanned yet.
+    /// The last scanned line is counted again, because it may be extended on the follow mode.
+    pub fn scan(&mut self, source: &dyn LineSource, searcher: &dyn Search) {
+        if self.scanned() > source.len() {
+            // the source was truncated
+            self.reset();
+        }
+        // the last line may be extended after it was scanned
+        if self.scanned() > 0 {
+            self.prefix.pop();
+        }
+        let start = self.scanned();
+        let end = cmp::min(start + COUNT_CHUNK_LINES, source.len());
+        let lines = source.lines(start..end);
+        if lines.is_empty() {
+            // the lines can't be read, e.g. the file was truncated before the update.
+            // they are counted as no matches so that the scan finishes.
+            let total = self.total();
+            self.prefix.resize(end + 1, total);
+            return;
+        }
+        for (i, line) in lines.iter().enumerate() {
+            let text = ansi::expand_line(line, self.tab_width, self.raw_control_chars).text;
+            let n = searcher
+                .find_iter_in(&text, source, start + i)
+                .filter(|m| !m.is_continued())
+                .count();
+            let total = self.total();
+            self.prefix.push(total + n);
+        }
+    }
+
+    /// Return the number of matches counted so far.
+    pub fn total(&self) -> usize {
+        *self.prefix.last().unwrap()
+    }
+
+    /// Return the index of the nth match of the line among all matches.
+    pub fn index_of(&self, lineno: usize, nth: usize) -> Option<usize> {
+        self.prefix.get(lineno).map(|n| n + nth)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::linesource::FileSource;
+    use crate::search::PlaneSearcher;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn test_count() {
+        let mut source: Vec<String> = (0..3000).map(|i| format!("{}", i % 10)).collect();
+        source[1] = "1 \x1b[31m1\x1b[0m".to_owned();
+        let mut searcher = PlaneSearcher::new();
+        searcher.set_pattern("1").unwrap();
+
+        let mut counter = MatchCounter::new(4, true);
+        assert!(!counter.is_complete(&source));
+        counter.scan(&source, &searcher);
+        assert_eq!(counter.scanned(), COUNT_CHUNK_LINES);
+        while !counter.is_complete(&source) {
+            counter.scan(&source, &searcher);
+        }
+        assert_eq!(counter.total(), 301);
+        assert_eq!(counter.index_of(1, 1), Some(1));
+        assert_eq!(counter.index_of(11, 0), Some(2));
+        assert_eq!(counter.index_of(3000, 0), Some(301));
+        assert_eq!(counter.index_of(3001, 0), None);
+
+        // the last line is extended
+        source[2999].push('1');
+        counter.scan(&source, &searcher);
+        assert!(counter.is_complete(&source));
+        assert_eq!(counter.total(), 302);
+
+        // truncated source
+        source.truncate(5);
+        counter.scan(&source, &searcher);
+        assert!(counter.is_complete(&source));
+        assert_eq!(counter.total(), 2);
+
+        counter.reset();
+        assert_eq!(counter.total(), 0);
+    }
+
+    #[test]
+    fn test_count_truncated_file() {
+        let mut path = env::temp_dir();
+        path.push(format!("peep-matchcount-{}", std::process::id()));
+        fs::write(&path, "1\n2\n1\n").unwrap();
+        let source = FileSource::open(path.to_str().unwrap()).unwrap();
+        source.sync();
+        let mut searcher = PlaneSearcher::new();
+        searcher.set_pattern("1").unwrap();
+
+        // the lines are indexed but can't be read after the truncation
+        fs::write(&path, "").unwrap();
+        let mut counter = MatchCounter::new(4, false);
+        counter.scan(&source, &searcher);
+        assert!(counter.is_complete(&source));
+        assert_eq!(counter.total(), 0);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
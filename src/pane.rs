@@ -1,9 +1,13 @@
 //! Pane module
 
 use crate::{
+    ansi::{self, Highlight, SgrText},
     csi::cursor_ext,
+    highlight::HighlightSet,
+    linesource::LineSource,
+    log::LogParser,
     search::{NullSearcher, Search},
-    tab::TabExpand,
+    syntax::SyntaxHighlighter,
     term,
     unicode_divide::UnicodeStrDivider,
 };
@@ -18,6 +22,14 @@ use unicode_width::UnicodeWidthStr;
 
 const DEFAULT_PANE_HEIGHT: u16 = 1;
 const DEFAULT_TAB_WIDTH: usize = 4;
+/// Colors of source tags (cyan, magenta, yellow, green, blue, red)
+const TAG_COLORS: [u8; 6] = [6, 5, 3, 2, 4, 1];
+/// Style of search matches (inverse)
+const MATCH_SGR: &str = "\x1b[7m";
+/// Style of the current search match (black on yellow)
+const CURRENT_MATCH_SGR: &str = "\x1b[30;43m";
+/// Style of marks on the gutter (inverse)
+const MARK_SGR: &str = "\x1b[7m";
 
 /// Display extention mark if the line doesn't fit in the pane width
 pub struct ExtendMark(pub char);
@@ -34,18 +46,35 @@ impl fmt::Display for ExtendMark {
 }
 
 pub struct Pane<'a> {
-    linebuf: Rc<RefCell<Vec<String>>>,
+    linebuf: Rc<RefCell<dyn LineSource>>,
     writer: Box<RefCell<dyn 'a + Write>>,
     height: u16,
+    // height before it is limited by the terminal height
+    requested_height: u16,
     numof_flushed_lines: u16,
-    numof_semantic_flushed_lines: u16,
-    cur_pos: (u16, u16), // (x, y)
+    numof_semantic_flushed_lines: usize,
+    cur_pos: (usize, usize), // (x, y)
     show_linenumber: bool,
     show_highlight: bool,
     hlsearcher: Rc<RefCell<dyn Search>>,
+    // patterns that are always highlighted with their colors
+    highlight_set: Rc<RefCell<HighlightSet>>,
+    log: Option<LogParser>,
+    syntax: Option<SyntaxHighlighter>,
     message: String,
     tab_width: usize,
     wraps_line: bool,
+    raw_control_chars: bool,
+    // terminal row of the top of the pane, if it is known
+    top_row: Option<u16>,
+    // line number and index of the current search match in the line
+    current_match: Option<(usize, usize)>,
+    // names of the marks and the lines of the file where they are set
+    marks: Vec<(char, usize)>,
+    // bytes of a line if the gutter shows the offsets of the hex view instead of line numbers
+    bytes_per_line: Option<usize>,
+    // terminal height at the last refresh
+    term_height: u16,
     term: Box<dyn TermStat>,
 }
 
@@ -81,13 +110,13 @@ impl TermStat for Terminal {
 
 #[derive(Debug)]
 pub enum ScrollStep {
-    Char(u16),
-    HalfPage(u16),
-    Page(u16),
+    Char(usize),
+    HalfPage(usize),
+    Page(usize),
 }
 
 impl ScrollStep {
-    fn to_numof_chars(&self, page_size: u16) -> u16 {
+    fn to_numof_chars(&self, page_size: usize) -> usize {
         match *self {
             ScrollStep::Char(n) => n,
             ScrollStep::HalfPage(n) => (page_size * n) / 2,
@@ -97,7 +126,7 @@ impl ScrollStep {
 }
 
 impl<'a> Pane<'a> {
-    const MARGIN_RIGHT_WIDTH: u16 = 4;
+    const MARGIN_RIGHT_WIDTH: usize = 4;
     const MESSAGE_BAR_HEIGHT: u16 = 1;
 
     pub fn new<W: 'a + Write>(w: Box<RefCell<W>>) -> Self {
@@ -105,15 +134,25 @@ impl<'a> Pane<'a> {
             linebuf: Rc::new(RefCell::new(Vec::new())),
             writer: w,
             height: DEFAULT_PANE_HEIGHT,
+            requested_height: DEFAULT_PANE_HEIGHT,
             numof_flushed_lines: 0,
             numof_semantic_flushed_lines: 0,
             cur_pos: (0, 0),
             show_linenumber: false,
             show_highlight: false,
             hlsearcher: Rc::new(RefCell::new(NullSearcher::new())),
+            highlight_set: Rc::new(RefCell::new(HighlightSet::new())),
+            log: None,
+            syntax: None,
             message: "".to_owned(),
             tab_width: DEFAULT_TAB_WIDTH,
             wraps_line: false,
+            raw_control_chars: false,
+            top_row: None,
+            current_match: None,
+            marks: Vec::new(),
+            bytes_per_line: None,
+            term_height: 0,
             term: Box::new(Terminal::new()),
         }
     }
@@ -129,9 +168,10 @@ impl<'a> Pane<'a> {
 
     /// Load text buffer and reset position.
     /// After this function is called, current pane postion is set to (0, 0).
-    pub fn load(&mut self, buf: Rc<RefCell<Vec<String>>>) {
+    pub fn load(&mut self, buf: Rc<RefCell<dyn LineSource>>) {
         self.linebuf = buf;
         self.cur_pos = (0, 0);
+        self.current_match = None;
     }
 
     fn flush(&self) {
@@ -166,14 +206,51 @@ impl<'a> Pane<'a> {
         Ok(())
     }
 
+    /// Return the ranges of the search matches, the highlight set, the log levels and the syntax.
+    /// The former ones are drawn over the latter ones where they overlap.
+    fn hl_ranges(&self, raw: &str, line_number: usize) -> Vec<Highlight> {
+        let mut layers = vec![];
+        if self.show_highlight {
+            layers.push(self.hl_match_ranges(raw, line_number));
+        }
+        layers.extend(self.highlight_set.borrow().layers(raw));
+        if let Some(ref log) = self.log {
+            layers.push(log.ranges(raw));
+        }
+        if let Some(ref syntax) = self.syntax {
+            layers.push(syntax.ranges(raw));
+        }
+        ansi::merge_highlights(layers)
+    }
+
     /// Return the range that matches the highlight word.
-    fn hl_match_ranges(&self, raw: &str) -> Vec<(usize, usize)> {
-        let mut v: Vec<(usize, usize)> = vec![];
+    /// The current match of the line is highlighted in the other style.
+    fn hl_match_ranges(&self, raw: &str, line_number: usize) -> Vec<Highlight> {
+        let mut v: Vec<Highlight> = vec![];
         if self.hlsearcher.borrow().as_str().is_empty() {
             return v;
         }
-        for m in self.hlsearcher.borrow().find_iter(raw) {
-            v.push((m.start(), m.end()));
+        let current = match self.current_match {
+            Some((n, nth)) if n == line_number => Some(nth),
+            _ => None,
+        };
+        let linebuf = self.linebuf.borrow();
+        let mut nth = 0;
+        for m in self
+            .hlsearcher
+            .borrow()
+            .find_iter_in(raw, &*linebuf, line_number)
+        {
+            // the rest of the match from the previous line is not the current one
+            let sgr = if !m.is_continued() && Some(nth) == current {
+                CURRENT_MATCH_SGR
+            } else {
+                MATCH_SGR
+            };
+            if !m.is_continued() {
+                nth += 1;
+            }
+            v.push(Highlight::new(m.start(), m.end(), sgr));
         }
         v
     }
@@ -182,14 +259,15 @@ impl<'a> Pane<'a> {
     fn hl_words_for_trimmed(
         trimmed: &str,
         trimrange: &(usize, usize),
-        hlranges: &[(usize, usize)],
+        hlranges: &[Highlight],
     ) -> String {
         let mut hlline = String::new();
         let mut copied = 0;
         let offset = trimrange.0;
         let end = trimrange.1 - offset;
 
-        for &(hl_s, hl_e) in hlranges.iter() {
+        for hl in hlranges.iter() {
+            let (hl_s, hl_e, sgr) = (hl.start, hl.end, &hl.sgr);
             if hl_e < trimrange.0 {
                 continue;
             } else if hl_s <= trimrange.0 && hl_e >= trimrange.1 {
@@ -197,19 +275,14 @@ impl<'a> Pane<'a> {
                 // [] : trimmed string
                 // __ : high-light string
                 // _[____]_
-                hlline.push_str(&format!(
-                    "{}{}{}",
-                    termion::style::Invert,
-                    trimmed,
-                    termion::style::Reset
-                ));
+                hlline.push_str(&format!("{}{}{}", sgr, trimmed, termion::style::Reset));
                 copied = end;
                 break;
             } else if hl_s <= trimrange.0 && hl_e > trimrange.0 {
                 // _[_   ]
                 hlline.push_str(&format!(
                     "{}{}{}",
-                    termion::style::Invert,
+                    sgr,
                     trimmed.get(..hl_e - offset).unwrap(),
                     termion::style::Reset
                 ));
@@ -219,7 +292,7 @@ impl<'a> Pane<'a> {
                 hlline.push_str(&format!(
                     "{}{}{}{}",
                     trimmed.get(copied..hl_s - offset).unwrap(),
-                    termion::style::Invert,
+                    sgr,
                     trimmed.get(hl_s - offset..hl_e - offset).unwrap(),
                     termion::style::Reset
                 ));
@@ -229,7 +302,7 @@ impl<'a> Pane<'a> {
                 hlline.push_str(&format!(
                     "{}{}{}{}",
                     trimmed.get(copied..hl_s - offset).unwrap(),
-                    termion::style::Invert,
+                    sgr,
                     trimmed.get(hl_s - offset..).unwrap(),
                     termion::style::Reset
                 ));
@@ -250,15 +323,52 @@ impl<'a> Pane<'a> {
         hlline
     }
 
+    /// Return the printable text of the line as shown on the pane.
+    fn expand_line(&self, raw: &str) -> SgrText {
+        ansi::expand_line(raw, self.tab_width, self.raw_control_chars)
+    }
+
+    /// Decorate trimmed string with SGR sequences and highlights
+    fn decorate_trimmed(
+        &self,
+        line: &SgrText,
+        trimmed: &str,
+        trimrange: &(usize, usize),
+        hlranges: &[Highlight],
+    ) -> String {
+        if !line.sgrs.is_empty() {
+            line.decorate(*trimrange, hlranges)
+        } else if !hlranges.is_empty() {
+            Self::hl_words_for_trimmed(trimmed, trimrange, hlranges)
+        } else {
+            trimmed.to_owned()
+        }
+    }
+
+    /// Return the display width of the line.
+    fn text_width(&self, raw: &str) -> usize {
+        UnicodeWidthStr::width(self.expand_line(raw).text.as_str())
+    }
+
     /// Generate line number string
     /// | 100 ......
     /// | 101 ......
-    fn gen_line_number_string(width: usize, line_number: u16) -> String {
-        match width {
-            0..=2 => format!("{:>2}", line_number + 1),
-            3 => format!("{:>3}", line_number + 1),
-            4 => format!("{:>4}", line_number + 1),
-            _ => format!("{:>5}", line_number + 1),
+    fn gen_line_number_string(width: usize, line_number: usize) -> String {
+        format!("{:>width$}", line_number + 1, width = width)
+    }
+
+    /// Generate offset string of the hex view
+    /// | 00000100: ......
+    /// | 00000110: ......
+    fn gen_offset_string(width: usize, offset: usize) -> String {
+        format!("{:0width$x}:", offset, width = width - 1)
+    }
+
+    /// Generate the string of the gutter: the line number or the offset of the line
+    fn gen_gutter_number_string(&self, width: usize, origin_line: usize) -> String {
+        match self.bytes_per_line {
+            Some(n) => Self::gen_offset_string(width, origin_line * n),
+            None => Self::gen_line_number_string(width, origin_line),
         }
     }
 
@@ -268,46 +378,78 @@ impl<'a> Pane<'a> {
     /// | 101 ......
     fn gen_blank_line_number_string(width: usize) -> String {
         // from the second line
-        match width {
-            0..=2 => "  ".to_owned(),
-            3 => "   ".to_owned(),
-            4 => "    ".to_owned(),
-            _ => "     ".to_owned(),
+        " ".repeat(width)
+    }
+
+    /// Generate mark string of the line if any mark is set
+    /// |a 12 xxxx......
+    /// |  13 xxxx......
+    fn gen_mark_string(&self, origin_line: usize) -> String {
+        if self.mark_printing_width() == 0 {
+            return String::new();
+        }
+        match self.marks.iter().find(|(_, line)| *line == origin_line) {
+            Some((name, _)) => format!("{}{}{}", MARK_SGR, name, termion::style::Reset),
+            None => " ".to_owned(),
+        }
+    }
+
+    /// Generate source tag string if the buffer is merged from several sources
+    /// | 12 a.log xxxx......
+    /// | 13 b.log xxxx......
+    fn gen_tag_string(&self, line_number: usize) -> String {
+        let width = self.linebuf.borrow().tag_width();
+        match self.linebuf.borrow().tag(line_number) {
+            Some((origin, tag)) => format!(
+                "{}{}{}",
+                termion::color::Fg(termion::color::AnsiValue(
+                    TAG_COLORS[origin % TAG_COLORS.len()]
+                )),
+                Self::gen_padded_tag_string(&tag, width),
+                termion::style::Reset
+            ),
+            None => String::new(),
         }
     }
 
+    /// Generate tag string that is padded to the width with a separator.
+    fn gen_padded_tag_string(tag: &str, width: usize) -> String {
+        let pad = width.saturating_sub(UnicodeWidthStr::width(tag));
+        format!(" {}{}", tag, " ".repeat(pad))
+    }
+
     /// Decorate line
     ///
     /// | 12+xxxxxxxxxxxxxxxxxxxxxxxxxx+|
     /// | 13+xxxxxxx.                   |
     /// | 14+xxxxxxxxxxxx.              |
     ///
-    fn decorate_trim(&self, raw: &str, line_number: u16) -> String {
+    fn decorate_trim(&self, raw: &str, line_number: usize) -> String {
         // subtract line number space from raw_range
         let lnpw = self.line_number_printing_width();
 
         // replace tabs with spaces
-        let raw_notab = raw.expand_tab(self.tab_width);
+        let line = self.expand_line(raw);
+        let raw_notab = &line.text;
 
         // trim unicode str considering visual unicode width
-        let mut ucdiv = UnicodeStrDivider::new(&raw_notab, self.width_of_text_area());
-        let _ = ucdiv.seek(SeekFrom::Start(u64::from(self.cur_pos.0)));
+        let mut ucdiv = UnicodeStrDivider::new(raw_notab, self.width_of_text_area());
+        let _ = ucdiv.seek(SeekFrom::Start(self.cur_pos.0 as u64));
         let trimmed = ucdiv.next().unwrap_or("");
         let uc_range = ucdiv.last_range();
 
         // highlight line
-        let hlline;
-        let decorated = if self.show_highlight {
-            let hl_ranges = self.hl_match_ranges(&raw_notab);
-            hlline = Self::hl_words_for_trimmed(trimmed, &uc_range, &hl_ranges);
-            &hlline
-        } else {
-            trimmed
-        };
+        let hl_ranges = self.hl_ranges(raw_notab, line_number);
+        let decorated = self.decorate_trimmed(&line, trimmed, &uc_range, &hl_ranges);
 
         // add line number
         let lnum = if self.show_linenumber {
-            Self::gen_line_number_string(lnpw, line_number)
+            let origin_line = self.linebuf.borrow().origin_line(line_number);
+            format!(
+                "{}{}",
+                self.gen_mark_string(origin_line),
+                self.gen_gutter_number_string(lnpw, origin_line)
+            )
         } else {
             String::new()
         };
@@ -328,7 +470,14 @@ impl<'a> Pane<'a> {
             format!("{}", termion::style::Reset)
         };
 
-        format!("{}{}{}{}", lnum, sol, decorated, eol)
+        format!(
+            "{}{}{}{}{}",
+            lnum,
+            self.gen_tag_string(line_number),
+            sol,
+            decorated,
+            eol
+        )
     }
 
     /// Decorate line
@@ -337,7 +486,7 @@ impl<'a> Pane<'a> {
     /// |   >xxxxxxx.                   |
     /// | 13 xxxxxxxxxxxx.              |
     ///
-    fn decorate_wrap(&self, raw: &str, line_number: u16) -> String {
+    fn decorate_wrap(&self, raw: &str, line_number: usize) -> String {
         let lnpw = if self.show_linenumber {
             self.line_number_printing_width()
         } else {
@@ -347,23 +496,41 @@ impl<'a> Pane<'a> {
         let line_cap_width = self.width_of_text_area();
 
         // replace tabs with spaces
-        let raw_notab = raw.expand_tab(self.tab_width);
+        let line = self.expand_line(raw);
+        let raw_notab = &line.text;
 
-        let mut ucdiv = UnicodeStrDivider::new(&raw_notab, self.width_of_text_area());
+        let mut ucdiv = UnicodeStrDivider::new(raw_notab, self.width_of_text_area());
+        let hl_ranges = self.hl_ranges(raw_notab, line_number);
 
         let mut s = 0;
         let mut e = line_cap_width;
 
         let mut wrapped = String::new();
-        let fn_lnum_string = |show_linenumber, width, start_pos, line_number| -> String {
-            if show_linenumber {
+        let tag = self.gen_tag_string(line_number);
+        let blank_tag = if tag.is_empty() {
+            String::new()
+        } else {
+            " ".repeat(self.tag_printing_width())
+        };
+        let origin_line = self.linebuf.borrow().origin_line(line_number);
+        let fn_lnum_string = |show_linenumber, width, start_pos| -> String {
+            let lnum = if show_linenumber {
                 if start_pos == 0 {
-                    Self::gen_line_number_string(width, line_number)
+                    format!(
+                        "{}{}",
+                        self.gen_mark_string(origin_line),
+                        self.gen_gutter_number_string(width, origin_line)
+                    )
                 } else {
-                    Self::gen_blank_line_number_string(width)
+                    Self::gen_blank_line_number_string(width + self.mark_printing_width())
                 }
             } else {
                 String::new()
+            };
+            if start_pos == 0 {
+                format!("{}{}", lnum, tag)
+            } else {
+                format!("{}{}", lnum, blank_tag)
             }
         };
 
@@ -371,17 +538,10 @@ impl<'a> Pane<'a> {
             let uc_range = ucdiv.last_range();
 
             // highlight line
-            let hlline;
-            let decorated = if self.show_highlight {
-                let hl_ranges = self.hl_match_ranges(&raw_notab);
-                hlline = Self::hl_words_for_trimmed(trimmed, &uc_range, &hl_ranges);
-                &hlline
-            } else {
-                trimmed
-            };
+            let decorated = self.decorate_trimmed(&line, trimmed, &uc_range, &hl_ranges);
 
             // add line number
-            let lnum = fn_lnum_string(self.show_linenumber, lnpw, s, line_number);
+            let lnum = fn_lnum_string(self.show_linenumber, lnpw, s);
             // add wrap marks
             let sol = if s > 0 {
                 format!("{}", ExtendMark('+'))
@@ -397,14 +557,14 @@ impl<'a> Pane<'a> {
 
         if wrapped.is_empty() {
             // add line number
-            let lnum = fn_lnum_string(self.show_linenumber, lnpw, s, line_number);
+            let lnum = fn_lnum_string(self.show_linenumber, lnpw, s);
             wrapped.push_str(&format!("{}\n", lnum));
         }
 
         wrapped
     }
 
-    fn decorate(&self, raw: &str, line_number: u16) -> String {
+    fn decorate(&self, raw: &str, line_number: usize) -> String {
         if self.wraps_line {
             self.decorate_wrap(raw, line_number)
         } else {
@@ -420,13 +580,11 @@ impl<'a> Pane<'a> {
         let mut block = String::new();
         let mut flushed_line_count = 0;
 
-        'outer: for (i, line) in self.linebuf.borrow()[buf_range.start..buf_range.end]
-            .iter()
-            .enumerate()
-        {
-            let deco = self.decorate(line, (buf_range.start + i) as u16);
+        let lines = self.linebuf.borrow().lines(buf_range.clone());
+        'outer: for (i, line) in lines.iter().enumerate() {
+            let deco = self.decorate(line, buf_range.start + i);
             let br = BufReader::new(deco.as_bytes());
-            self.numof_semantic_flushed_lines = i as u16 + 1;
+            self.numof_semantic_flushed_lines = i + 1;
             for lline in br.lines() {
                 block.push_str(&format!("{}\n", lline?));
                 flushed_line_count += 1;
@@ -437,7 +595,7 @@ impl<'a> Pane<'a> {
         }
 
         // move down to message bar position
-        let numof_lines_to_message_bar = pane_height - flushed_line_count as u16;
+        let numof_lines_to_message_bar = pane_height - flushed_line_count;
         if numof_lines_to_message_bar > 0 {
             block.push_str(&format!(
                 "{}",
@@ -446,7 +604,7 @@ impl<'a> Pane<'a> {
         }
 
         // message line
-        if self.message.is_empty() && buf_range.start >= self.limit_bottom_y()? as usize {
+        if self.message.is_empty() && buf_range.start >= self.limit_bottom_y()? {
             block.push_str(&format!(
                 "{}(END){}",
                 termion::style::Invert,
@@ -457,7 +615,9 @@ impl<'a> Pane<'a> {
         };
 
         self.return_home();
-        self.sweep(cmp::max(self.numof_flushed_lines, pane_height));
+        let numof_sweep_lines = cmp::max(self.numof_flushed_lines, pane_height);
+        self.sweep(numof_sweep_lines);
+        self.scroll_top_row(numof_sweep_lines)?;
         self.writer
             .borrow_mut()
             .write_all(block.as_bytes())
@@ -467,6 +627,31 @@ impl<'a> Pane<'a> {
         Ok(())
     }
 
+    /// Update the top row after n lines are swept.
+    /// The terminal scrolls up if the lines overflow the bottom of it.
+    fn scroll_top_row(&mut self, n: u16) -> io::Result<()> {
+        let term_height = (*self.term).size()?.1;
+        if let Some(top) = self.top_row {
+            self.top_row = Some(cmp::min(top, term_height.saturating_sub(n)));
+        }
+        self.term_height = term_height;
+        Ok(())
+    }
+
+    /// Set the terminal row (1-based) where the message bar is currently drawn.
+    pub fn locate_message_bar(&mut self, row: u16) -> io::Result<()> {
+        self.top_row = Some(row.saturating_sub(self.pane_size()?.1));
+        Ok(())
+    }
+
+    /// Return whether the terminal row (1-based) is on the message bar.
+    pub fn is_message_bar_row(&self, row: u16) -> bool {
+        match (self.top_row, self.pane_size()) {
+            (Some(top), Ok((_, pane_height))) => top + pane_height == row,
+            _ => false,
+        }
+    }
+
     pub fn quit(&self) {
         write!(
             self.writer.borrow_mut(),
@@ -479,11 +664,39 @@ impl<'a> Pane<'a> {
     }
 
     fn line_number_printing_width(&self) -> usize {
-        match self.linebuf.borrow().len() {
-            0..=99 => 2,
-            100..=999 => 3,
-            1000..=9999 => 4,
-            _ => 5,
+        if let Some(n) = self.bytes_per_line {
+            // hex digits of the last offset, at least 8, and the colon
+            let last_offset = self.linebuf.borrow().len().saturating_sub(1) * n;
+            return cmp::max(format!("{:x}", last_offset).len(), 8) + 1;
+        }
+        // number of decimal digits of the last line number, at least 2
+        let mut width = 1;
+        let last_line_number = match self.linebuf.borrow().len() {
+            0 => 0,
+            len => self.linebuf.borrow().origin_line(len - 1) + 1,
+        };
+        let mut n = last_line_number / 10;
+        while n > 0 {
+            width += 1;
+            n /= 10;
+        }
+        cmp::max(width, 2)
+    }
+
+    /// Return the width of mark column. It is shown with line numbers if any mark is set.
+    fn mark_printing_width(&self) -> usize {
+        if self.show_linenumber && !self.marks.is_empty() {
+            1
+        } else {
+            0
+        }
+    }
+
+    /// Return the width of source tag column including the separator.
+    fn tag_printing_width(&self) -> usize {
+        match self.linebuf.borrow().tag_width() {
+            0 => 0,
+            w => w + 1,
         }
     }
 
@@ -499,6 +712,32 @@ impl<'a> Pane<'a> {
         self.hlsearcher = searcher;
     }
 
+    /// Set the marks to show on the gutter as the names and the lines of the file.
+    pub fn set_marks(&mut self, mut marks: Vec<(char, usize)>) {
+        marks.sort_unstable();
+        self.marks = marks;
+    }
+
+    /// Show the offsets of the lines on the gutter instead of the line numbers,
+    /// where every line has the bytes. None shows the line numbers.
+    pub fn set_offset_gutter(&mut self, bytes_per_line: Option<usize>) {
+        self.bytes_per_line = bytes_per_line;
+    }
+
+    /// Set the parser that colors the levels and the timestamps of log lines, or None.
+    pub fn set_log(&mut self, log: Option<LogParser>) {
+        self.log = log;
+    }
+
+    /// Set the syntax highlighter of the text, or None for the plain text.
+    pub fn set_syntax(&mut self, syntax: Option<SyntaxHighlighter>) {
+        self.syntax = syntax;
+    }
+
+    pub fn set_highlight_set(&mut self, set: Rc<RefCell<HighlightSet>>) {
+        self.highlight_set = set;
+    }
+
     pub fn set_message(&mut self, msg: Option<String>) {
         if let Some(m) = msg {
             self.message = m;
@@ -526,37 +765,32 @@ impl<'a> Pane<'a> {
     }
 
     /// Return (x, y)
-    pub fn position(&self) -> (u16, u16) {
+    pub fn position(&self) -> (usize, usize) {
         self.cur_pos
     }
 
     /// Return logical lines (wrapped lines) of specified line number.
-    fn count_wrapped_lines(&self, text: &str) -> u16 {
+    fn count_wrapped_lines(&self, text: &str) -> usize {
         let pane_width = self.width_of_text_area();
-        if pane_width == 0 {
-            0
-        } else {
-            (UnicodeWidthStr::width(text) / pane_width) as u16 + 1
-        }
+        self.text_width(text)
+            .checked_div(pane_width)
+            .map_or(0, |n| n + 1)
     }
 
     /// Return the end of y that is considered buffer lines and window size and wrapped lines.
-    fn limit_bottom_y(&self) -> io::Result<u16> {
-        let linebuf_height = self.linebuf.borrow().len() as u16;
-        let pane_height = self.pane_size()?.1;
+    fn limit_bottom_y(&self) -> io::Result<usize> {
+        let linebuf_height = self.linebuf.borrow().len();
+        let pane_height = self.pane_size()?.1 as usize;
 
         if !self.wraps_line {
-            return Ok(if linebuf_height > pane_height {
-                linebuf_height - pane_height
-            } else {
-                0
-            });
+            return Ok(linebuf_height.saturating_sub(pane_height));
         }
 
         // self.wraps_line is enabled
         let mut sum = 0;
         for i in (0..linebuf_height).rev() {
-            sum += self.count_wrapped_lines(&self.linebuf.borrow()[i as usize]);
+            let line = self.linebuf.borrow().line(i).unwrap_or_default();
+            sum += self.count_wrapped_lines(&line);
             if sum > pane_height {
                 return Ok(if i == linebuf_height {
                     linebuf_height
@@ -576,7 +810,8 @@ impl<'a> Pane<'a> {
             self.line_number_printing_width()
         } else {
             0
-        };
+        } + self.mark_printing_width()
+            + self.tag_printing_width();
 
         if pane_width > lnpw + extend_mark_space {
             pane_width - lnpw - extend_mark_space
@@ -589,35 +824,35 @@ impl<'a> Pane<'a> {
     fn range_of_visible_lines(&self) -> io::Result<ops::Range<usize>> {
         let pane_height = self.pane_size()?.1 as usize;
         let buf_height = self.linebuf.borrow().len();
-        let y = self.cur_pos.1 as usize;
+        let y = self.cur_pos.1;
 
-        Ok(y..if (buf_height - y) < pane_height {
-            buf_height
-        } else {
-            y + pane_height
-        })
+        Ok(y..cmp::min(buf_height, y + pane_height))
     }
 
     /// Return max width of linebuf range
-    fn max_width_of_visible_lines(&self, r: ops::Range<usize>) -> u16 {
-        self.linebuf.borrow()[r]
+    fn max_width_of_visible_lines(&self, r: ops::Range<usize>) -> usize {
+        self.linebuf
+            .borrow()
+            .lines(r)
             .iter()
-            .map(|s| UnicodeWidthStr::width(s.as_str()))
-            .fold(0, cmp::max) as u16
+            .map(|s| self.text_width(s))
+            .fold(0, cmp::max)
     }
 
     /// Return the pane printable width
-    fn pane_printable_width(&self) -> io::Result<u16> {
-        Ok(self.pane_size()?.0
-            - if self.show_linenumber {
-                self.line_number_printing_width() as u16
+    fn pane_printable_width(&self) -> io::Result<usize> {
+        Ok((self.pane_size()?.0 as usize).saturating_sub(
+            if self.show_linenumber {
+                self.line_number_printing_width()
             } else {
                 0
-            })
+            } + self.mark_printing_width()
+                + self.tag_printing_width(),
+        ))
     }
 
     /// Return the horizontal offset that is considered pane size and string length
-    fn limit_right_x(&self, next_x: u16, max_len: u16) -> io::Result<u16> {
+    fn limit_right_x(&self, next_x: usize, max_len: usize) -> io::Result<usize> {
         let margined_len = max_len + Self::MARGIN_RIGHT_WIDTH;
         let pane_width = self.pane_printable_width()?;
         Ok(if pane_width >= margined_len {
@@ -630,7 +865,7 @@ impl<'a> Pane<'a> {
     }
 
     // return actual scroll distance
-    pub fn scroll_up(&mut self, ss: &ScrollStep) -> io::Result<u16> {
+    pub fn scroll_up(&mut self, ss: &ScrollStep) -> io::Result<usize> {
         let step = ss.to_numof_chars(self.numof_semantic_flushed_lines);
         let astep = if self.cur_pos.1 > step {
             step
@@ -642,22 +877,20 @@ impl<'a> Pane<'a> {
     }
 
     // return actual scroll distance
-    pub fn scroll_down(&mut self, ss: &ScrollStep) -> io::Result<u16> {
+    pub fn scroll_down(&mut self, ss: &ScrollStep) -> io::Result<usize> {
         let step = ss.to_numof_chars(self.numof_semantic_flushed_lines);
         let end_y = self.limit_bottom_y()?;
         let astep = if end_y > self.cur_pos.1 + step {
             step
-        } else if end_y > self.cur_pos.1 {
-            end_y - self.cur_pos.1
         } else {
-            0
+            end_y.saturating_sub(self.cur_pos.1)
         };
         self.cur_pos.1 += astep;
         Ok(astep)
     }
 
     // return actual scroll distance
-    pub fn scroll_left(&mut self, ss: &ScrollStep) -> io::Result<u16> {
+    pub fn scroll_left(&mut self, ss: &ScrollStep) -> io::Result<usize> {
         if self.wraps_line {
             return Ok(0);
         }
@@ -672,7 +905,7 @@ impl<'a> Pane<'a> {
     }
 
     // return actual scroll distance
-    pub fn scroll_right(&mut self, ss: &ScrollStep) -> io::Result<u16> {
+    pub fn scroll_right(&mut self, ss: &ScrollStep) -> io::Result<usize> {
         if self.wraps_line {
             return Ok(0);
         }
@@ -684,19 +917,19 @@ impl<'a> Pane<'a> {
         Ok(astep)
     }
 
-    pub fn goto_top_of_lines(&mut self) -> io::Result<(u16, u16)> {
+    pub fn goto_top_of_lines(&mut self) -> io::Result<(usize, usize)> {
         self.cur_pos = (0, 0);
         Ok(self.cur_pos)
     }
 
-    pub fn goto_bottom_of_lines(&mut self) -> io::Result<(u16, u16)> {
+    pub fn goto_bottom_of_lines(&mut self) -> io::Result<(usize, usize)> {
         let y = self.limit_bottom_y().unwrap();
         self.cur_pos = (0, y);
         Ok(self.cur_pos)
     }
 
     /// Go to head of current line.
-    pub fn goto_head_of_line(&mut self) -> io::Result<(u16, u16)> {
+    pub fn goto_head_of_line(&mut self) -> io::Result<(usize, usize)> {
         if !self.wraps_line {
             self.cur_pos.0 = 0;
         }
@@ -704,7 +937,7 @@ impl<'a> Pane<'a> {
     }
 
     /// Go to tail of current line.
-    pub fn goto_tail_of_line(&mut self) -> io::Result<(u16, u16)> {
+    pub fn goto_tail_of_line(&mut self) -> io::Result<(usize, usize)> {
         if !self.wraps_line {
             let max_line_width = self.max_width_of_visible_lines(self.range_of_visible_lines()?);
             self.cur_pos.0 = self.limit_right_x(max_line_width, max_line_width).unwrap();
@@ -714,17 +947,17 @@ impl<'a> Pane<'a> {
 
     /// Go to specified absolute line number.
     /// Scroll so that the specified line appears at the top of the pane.
-    pub fn goto_absolute_line(&mut self, lineno: u16) -> io::Result<u16> {
-        let buf_height = self.linebuf.borrow().len() as u16;
+    pub fn goto_absolute_line(&mut self, lineno: usize) -> io::Result<usize> {
+        let buf_height = self.linebuf.borrow().len();
         self.cur_pos.1 = if lineno >= buf_height {
-            buf_height - 1
+            buf_height.saturating_sub(1)
         } else {
             lineno
         };
         Ok(self.cur_pos.1)
     }
 
-    pub fn goto_absolute_horizontal_offset(&mut self, offset: u16) -> io::Result<u16> {
+    pub fn goto_absolute_horizontal_offset(&mut self, offset: usize) -> io::Result<usize> {
         if !self.wraps_line {
             let max_line_width = self.max_width_of_visible_lines(self.range_of_visible_lines()?);
             self.cur_pos.0 = self.limit_right_x(offset, max_line_width)?;
@@ -732,10 +965,58 @@ impl<'a> Pane<'a> {
         Ok(self.cur_pos.0)
     }
 
+    /// Return the current search match as the line number and the index in the line.
+    pub fn current_match(&self) -> Option<(usize, usize)> {
+        self.current_match
+    }
+
+    pub fn set_current_match(&mut self, m: Option<(usize, usize)>) {
+        self.current_match = m;
+    }
+
+    /// Go to the nth match of the line, and make it the current match.
+    /// The pane scrolls horizontally so that the match is visible.
+    pub fn goto_match(&mut self, lineno: usize, nth: usize) -> io::Result<()> {
+        self.current_match = Some((lineno, nth));
+        self.goto_absolute_line(lineno)?;
+        if self.wraps_line {
+            return Ok(());
+        }
+
+        let raw = self.linebuf.borrow().line(lineno).unwrap_or_default();
+        let text = self.expand_line(&raw).text;
+        let m = self
+            .hlsearcher
+            .borrow()
+            .find_iter_in(&text, &*self.linebuf.borrow(), lineno)
+            .filter(|m| !m.is_continued())
+            .nth(nth);
+        let m = match m {
+            Some(m) => m,
+            None => return Ok(()),
+        };
+        let start = UnicodeWidthStr::width(&text[..m.start()]);
+        let end = UnicodeWidthStr::width(&text[..m.end()]);
+        let width = self.width_of_text_area();
+        if start < self.cur_pos.0 || end > self.cur_pos.0 + width {
+            // put the match on the middle of the pane
+            self.goto_absolute_horizontal_offset(start.saturating_sub(width / 2))?;
+        }
+        Ok(())
+    }
+
+    /// Return whether the line is on the pane.
+    pub fn is_line_visible(&self, lineno: usize) -> bool {
+        self.range_of_visible_lines()
+            .map(|r| r.contains(&lineno))
+            .unwrap_or(false)
+    }
+
     /// Set pane height.
     /// Pane height is limited by the actual terminal height.
     /// Return acutually set pane height.
     pub fn set_height(&mut self, n: u16) -> io::Result<u16> {
+        self.requested_height = n;
         let max = (*self.term).size()?.1 - Self::MESSAGE_BAR_HEIGHT;
         self.height = if n == 0 {
             1
@@ -750,7 +1031,7 @@ impl<'a> Pane<'a> {
     /// Increment pane height.
     /// Return acutually set pane height.
     pub fn increment_height(&mut self, n: u16) -> io::Result<u16> {
-        let height = self.height + n;
+        let height = self.height.saturating_add(n);
         self.set_height(height)
     }
 
@@ -761,11 +1042,48 @@ impl<'a> Pane<'a> {
         self.set_height(height)
     }
 
+    /// Fit the pane to the resized terminal.
+    /// The old content is swept, and the pane height is limited by the new terminal height
+    /// or restored up to the requested height.
+    pub fn fit_to_terminal(&mut self) -> io::Result<()> {
+        let term_height = (*self.term).size()?.1;
+        let old_pane_height = self.height;
+
+        // lines beyond the terminal are already scrolled out
+        self.numof_flushed_lines = cmp::min(
+            self.numof_flushed_lines,
+            term_height.saturating_sub(Self::MESSAGE_BAR_HEIGHT),
+        );
+        self.return_home();
+        self.sweep(self.numof_flushed_lines);
+        self.numof_flushed_lines = 0;
+
+        let requested_height = self.requested_height;
+        self.set_height(requested_height)?;
+        self.requested_height = requested_height;
+        self.cur_pos.1 = cmp::min(self.cur_pos.1, self.limit_bottom_y()?);
+
+        // the message bar at the bottom of the terminal stays at the bottom
+        if let Some(top) = self.top_row {
+            if top + old_pane_height >= self.term_height {
+                self.top_row = Some(term_height.saturating_sub(self.pane_size()?.1));
+            }
+        }
+        self.term_height = term_height;
+        Ok(())
+    }
+
     /// Set tab width.
     pub fn set_tab_width(&mut self, w: u16) {
         self.tab_width = w as usize;
     }
 
+    /// Set raw control chars option.
+    /// SGR sequences of lines are output and the others are dropped.
+    pub fn set_raw_control_chars(&mut self, b: bool) {
+        self.raw_control_chars = b;
+    }
+
     /// Set wrap-line option.
     pub fn set_wrap(&mut self, b: bool) {
         self.wraps_line = b;
@@ -778,6 +1096,8 @@ impl<'a> Pane<'a> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::search::{RegexSearcher, SearchMode};
+    use crate::syntax::Language;
     use std::fs::OpenOptions;
     use std::io::BufWriter;
 
@@ -832,8 +1152,8 @@ mod tests {
         // to update numof_semantic_flushed_lines
         let _ = pane.refresh();
 
-        let stride_page = pane_height;
-        let stride_hpage = pane_height / 2;
+        let stride_page = pane_height as usize;
+        let stride_hpage = pane_height as usize / 2;
 
         // in range
         {
@@ -885,16 +1205,16 @@ mod tests {
         {
             assert_eq!(
                 pane.scroll_down(&ScrollStep::Page(10)).unwrap(),
-                texts.borrow().len() as u16 - pane_height
+                texts.borrow().len() - pane_height as usize
             );
             assert_eq!(
                 pane.position(),
-                (0, texts.borrow().len() as u16 - pane_height)
+                (0, texts.borrow().len() - pane_height as usize)
             );
 
             assert_eq!(
                 pane.scroll_up(&ScrollStep::Page(10)).unwrap(),
-                texts.borrow().len() as u16 - pane_height
+                texts.borrow().len() - pane_height as usize
             );
             assert_eq!(pane.position(), (0, 0));
         }
@@ -910,8 +1230,8 @@ mod tests {
         let height: u16 = 2;
         pane.replace_termsize_getter(Box::new(TestTerminal::new(width, height)));
 
-        let stride_page = width;
-        let stride_hpage = width / 2;
+        let stride_page = width as usize;
+        let stride_hpage = width as usize / 2;
 
         // in range
         {
@@ -967,12 +1287,12 @@ mod tests {
             // need to consider right margin
             assert_eq!(
                 pane.scroll_right(&ScrollStep::Page(10)).unwrap(),
-                texts.borrow()[0].len() as u16 - width + Pane::MARGIN_RIGHT_WIDTH
+                texts.borrow()[0].len() - width as usize + Pane::MARGIN_RIGHT_WIDTH
             );
             assert_eq!(
                 pane.position(),
                 (
-                    texts.borrow()[0].len() as u16 - width + Pane::MARGIN_RIGHT_WIDTH,
+                    texts.borrow()[0].len() - width as usize + Pane::MARGIN_RIGHT_WIDTH,
                     0
                 )
             );
@@ -1007,11 +1327,11 @@ mod tests {
         pane.scroll_right(&ScrollStep::Char(1)).unwrap();
         assert_eq!(
             pane.goto_bottom_of_lines().unwrap(),
-            (0, texts.borrow().len() as u16 - pane_height)
+            (0, texts.borrow().len() - pane_height as usize)
         );
         assert_eq!(
             pane.position(),
-            (0, texts.borrow().len() as u16 - pane_height)
+            (0, texts.borrow().len() - pane_height as usize)
         );
 
         pane.scroll_right(&ScrollStep::Char(1)).unwrap();
@@ -1024,9 +1344,9 @@ mod tests {
         assert_eq!(pane.position(), (0, 0));
         assert_eq!(
             pane.goto_absolute_line(100).unwrap(),
-            texts.borrow().len() as u16 - 1
+            texts.borrow().len() - 1
         );
-        assert_eq!(pane.position(), (0, texts.borrow().len() as u16 - 1));
+        assert_eq!(pane.position(), (0, texts.borrow().len() - 1));
 
         // case: buffer height is less than pane height
         let t = ["", "", "", ""];
@@ -1061,7 +1381,7 @@ mod tests {
         assert_eq!(
             pane.goto_tail_of_line().unwrap(),
             (
-                texts.borrow()[0].len() as u16 - width + Pane::MARGIN_RIGHT_WIDTH,
+                texts.borrow()[0].len() - width as usize + Pane::MARGIN_RIGHT_WIDTH,
                 0
             )
         );
@@ -1072,7 +1392,7 @@ mod tests {
         assert_eq!(pane.position(), (0, 0));
         assert_eq!(
             pane.goto_absolute_horizontal_offset(100).unwrap(),
-            texts.borrow()[0].len() as u16 - width + Pane::MARGIN_RIGHT_WIDTH
+            texts.borrow()[0].len() - width as usize + Pane::MARGIN_RIGHT_WIDTH
         );
     }
 
@@ -1139,6 +1459,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn test_show_highlight() {
         let mut pane = gen_pane!(OpenOptions::new().write(true).open("/dev/null").unwrap());
         pane.show_highlight(true);
@@ -1148,6 +1469,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn test_show_line_number() {
         let mut pane = gen_pane!(OpenOptions::new().write(true).open("/dev/null").unwrap());
         pane.show_line_number(true);
@@ -1209,7 +1531,7 @@ mod tests {
         let t = [
             "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "",
         ];
-        let nbuflines = t.len() as u16;
+        let nbuflines = t.len();
         let texts = gen_texts(&t);
         let mut pane = gen_pane!(OpenOptions::new().write(true).open("/dev/null").unwrap());
         pane.load(texts.clone());
@@ -1228,7 +1550,7 @@ mod tests {
         let t = [
             "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "",
         ];
-        let nbuflines = t.len() as u16;
+        let nbuflines = t.len();
         let texts = gen_texts(&t);
         let mut pane = gen_pane!(OpenOptions::new().write(true).open("/dev/null").unwrap());
         pane.load(texts.clone());
@@ -1247,10 +1569,213 @@ mod tests {
         );
         assert_eq!(
             pane.range_of_visible_lines().unwrap(),
-            (nbuflines as usize - 1)..(nbuflines as usize)
+            (nbuflines - 1)..nbuflines
+        );
+    }
+
+    #[test]
+    fn test_line_number_printing_width() {
+        let mut pane = gen_pane!(OpenOptions::new().write(true).open("/dev/null").unwrap());
+        let width: u16 = 20;
+        let height: u16 = 10;
+        pane.replace_termsize_getter(Box::new(TestTerminal::new(width, height)));
+
+        let data = [
+            (0, 2),
+            (99, 2),
+            (100, 3),
+            (99_999, 5),
+            (100_000, 6),
+            (1_234_567, 7),
+        ];
+        for &(nlines, expected) in data.iter() {
+            pane.load(Rc::new(RefCell::new(vec![String::new(); nlines])));
+            assert_eq!(pane.line_number_printing_width(), expected);
+        }
+
+        assert_eq!(Pane::gen_line_number_string(2, 0), " 1");
+        assert_eq!(Pane::gen_line_number_string(6, 99_999), "100000");
+        assert_eq!(Pane::gen_blank_line_number_string(6), "      ");
+    }
+
+    #[test]
+    fn test_offset_gutter() {
+        let mut pane = gen_pane!(OpenOptions::new().write(true).open("/dev/null").unwrap());
+        pane.replace_termsize_getter(Box::new(TestTerminal::new(40, 10)));
+        pane.load(gen_texts(&["foo", "bar"]));
+        pane.show_line_number(true);
+        pane.set_offset_gutter(Some(16));
+        assert_eq!(pane.line_number_printing_width(), 9);
+        assert!(pane.decorate_trim("bar", 1).starts_with("00000010: bar"));
+
+        assert_eq!(Pane::gen_offset_string(10, 0x1_0000_0000), "100000000:");
+
+        pane.set_offset_gutter(None);
+        pane.load(gen_texts(&["foo", "bar"]));
+        assert!(pane.decorate_trim("bar", 1).starts_with(" 2 bar"));
+    }
+
+    #[test]
+    fn test_mark_gutter() {
+        let mut pane = gen_pane!(OpenOptions::new().write(true).open("/dev/null").unwrap());
+        pane.replace_termsize_getter(Box::new(TestTerminal::new(20, 10)));
+        pane.load(gen_texts(&["foo", "bar"]));
+        pane.show_line_number(true);
+        assert_eq!(pane.width_of_text_area(), 16);
+
+        pane.set_marks(vec![('b', 1), ('a', 1)]);
+        assert_eq!(pane.width_of_text_area(), 15);
+        assert_eq!(pane.gen_mark_string(0), " ");
+        assert_eq!(pane.gen_mark_string(1), format!("{}a\x1b[m", MARK_SGR));
+        assert!(pane
+            .decorate_trim("bar", 1)
+            .starts_with(&format!("{}a\x1b[m 2 bar", MARK_SGR)));
+
+        // no mark column without line numbers
+        pane.show_line_number(false);
+        assert_eq!(pane.gen_mark_string(1), "");
+        assert_eq!(pane.width_of_text_area(), 18);
+    }
+
+    #[test]
+    fn test_goto_beyond_u16_lines() {
+        let nlines = 100_000;
+        let texts = Rc::new(RefCell::new(vec![String::new(); nlines]));
+        let mut pane = gen_pane!(OpenOptions::new().write(true).open("/dev/null").unwrap());
+        pane.load(texts);
+        let width: u16 = 2;
+        let height: u16 = 10;
+        pane.replace_termsize_getter(Box::new(TestTerminal::new(width, height)));
+        assert_eq!(pane.set_height(5).unwrap(), 5);
+
+        assert_eq!(pane.goto_absolute_line(70_000).unwrap(), 70_000);
+        assert_eq!(pane.range_of_visible_lines().unwrap(), 70_000..70_005);
+        assert_eq!(pane.scroll_down(&ScrollStep::Char(1)).unwrap(), 1);
+        assert_eq!(pane.position(), (0, 70_001));
+        assert_eq!(pane.goto_bottom_of_lines().unwrap(), (0, nlines - 5));
+    }
+
+    #[test]
+    fn test_tag_string() {
+        use crate::linesource::MergedSource;
+
+        let mut src = MergedSource::new(vec!["a".to_owned(), "bcd".to_owned()]);
+        src.push(0, "x".to_owned());
+        src.push(1, "y".to_owned());
+        let mut pane = gen_pane!(OpenOptions::new().write(true).open("/dev/null").unwrap());
+        pane.replace_termsize_getter(Box::new(TestTerminal::new(20, 10)));
+        pane.load(Rc::new(RefCell::new(src)));
+
+        assert_eq!(Pane::gen_padded_tag_string("a", 3), " a  ");
+        assert_eq!(pane.tag_printing_width(), 4);
+        // 20 - tag(4) - extend marks(2)
+        assert_eq!(pane.width_of_text_area(), 14);
+        assert_eq!(
+            pane.gen_tag_string(1),
+            format!(
+                "{} bcd{}",
+                termion::color::Fg(termion::color::AnsiValue(TAG_COLORS[1])),
+                termion::style::Reset
+            )
+        );
+
+        pane.load(gen_texts(&["x"]));
+        assert_eq!(pane.tag_printing_width(), 0);
+        assert_eq!(pane.gen_tag_string(0), "");
+    }
+
+    #[test]
+    fn test_raw_control_chars() {
+        let texts = gen_texts(&["ab\x1b[31mcdef\x1b[0mgh"]);
+        let mut pane = gen_pane!(OpenOptions::new().write(true).open("/dev/null").unwrap());
+        pane.replace_termsize_getter(Box::new(TestTerminal::new(6, 10)));
+        pane.load(texts);
+
+        // escape bytes are not counted as width
+        pane.set_raw_control_chars(true);
+        assert_eq!(pane.text_width("ab\x1b[31mcdef\x1b[0mgh"), 8);
+        assert_eq!(pane.max_width_of_visible_lines(0..1), 8);
+        // escape bytes are shown as `^[`
+        pane.set_raw_control_chars(false);
+        assert_eq!(pane.max_width_of_visible_lines(0..1), 19);
+
+        // color of trimmed head is carried over
+        pane.set_raw_control_chars(true);
+        pane.cur_pos.0 = 3;
+        let deco = pane.decorate_trim("ab\x1b[31mcdef\x1b[0mgh", 0);
+        assert!(deco.starts_with(&format!("{}\x1b[31mdef\x1b[0m", ExtendMark('+'))));
+    }
+
+    #[test]
+    fn test_control_chars() {
+        let raw = "a\x01b\u{10ffff}";
+        let mut pane = gen_pane!(OpenOptions::new().write(true).open("/dev/null").unwrap());
+        pane.replace_termsize_getter(Box::new(TestTerminal::new(20, 10)));
+        pane.load(gen_texts(&[raw]));
+        assert_eq!(pane.text_width(raw), 8);
+        assert_eq!(
+            pane.decorate_trim(raw, 0),
+            format!(
+                " a\x1b[7m^A\x1b[27mb\x1b[7m<FF>\x1b[m{}",
+                termion::style::Reset
+            )
         );
     }
 
+    #[test]
+    fn test_message_bar_row() {
+        let texts = gen_texts(&["a", "b", "c", "d", "e"]);
+        let mut pane = gen_pane!(OpenOptions::new().write(true).open("/dev/null").unwrap());
+        pane.replace_termsize_getter(Box::new(TestTerminal::new(10, 10)));
+        pane.load(texts);
+        let _ = pane.set_height(3);
+
+        // unknown position
+        assert!(!pane.is_message_bar_row(4));
+
+        // pane is on rows 2-4, message bar is on row 5
+        let _ = pane.locate_message_bar(5);
+        assert!(pane.is_message_bar_row(5));
+        let _ = pane.set_height(5);
+        let _ = pane.refresh();
+        assert!(pane.is_message_bar_row(7));
+
+        // pane overflows the terminal and it scrolls
+        let _ = pane.set_height(9);
+        let _ = pane.refresh();
+        assert!(pane.is_message_bar_row(10));
+        let _ = pane.set_height(2);
+        let _ = pane.refresh();
+        assert!(pane.is_message_bar_row(3));
+    }
+
+    #[test]
+    fn test_fit_to_terminal() {
+        let texts = gen_texts(&["a", "b", "c", "d", "e", "f", "g", "h", "i", "j"]);
+        let mut pane = gen_pane!(OpenOptions::new().write(true).open("/dev/null").unwrap());
+        pane.replace_termsize_getter(Box::new(TestTerminal::new(10, 10)));
+        pane.load(texts);
+        let _ = pane.set_height(6);
+        let _ = pane.goto_bottom_of_lines();
+        let _ = pane.locate_message_bar(10);
+        let _ = pane.refresh();
+        assert_eq!(pane.position(), (0, 4));
+
+        // shrink: the height is limited and the message bar stays at the bottom
+        pane.replace_termsize_getter(Box::new(TestTerminal::new(10, 4)));
+        let _ = pane.fit_to_terminal();
+        assert_eq!(pane.pane_size().unwrap().1, 3);
+        assert_eq!(pane.position(), (0, 4));
+        assert!(pane.is_message_bar_row(4));
+
+        // grow: the requested height is restored
+        pane.replace_termsize_getter(Box::new(TestTerminal::new(10, 20)));
+        let _ = pane.fit_to_terminal();
+        assert_eq!(pane.pane_size().unwrap().1, 6);
+        assert_eq!(pane.position(), (0, 4));
+        assert!(pane.is_message_bar_row(20));
+    }
+
     #[test]
     fn test_limit_right_x() {
         let mut pane = gen_pane!(OpenOptions::new().write(true).open("/dev/null").unwrap());
@@ -1269,7 +1794,121 @@ mod tests {
         assert_eq!(
             pane.limit_right_x(40, max_text_length).unwrap(),
             // remain 10
-            max_text_length - width + Pane::MARGIN_RIGHT_WIDTH
+            max_text_length - width as usize + Pane::MARGIN_RIGHT_WIDTH
+        );
+    }
+
+    #[test]
+    fn test_goto_match() {
+        let long = format!(
+            "{}foo{}foo{}",
+            "x".repeat(30),
+            "y".repeat(3),
+            "z".repeat(40)
+        );
+        let texts = gen_texts(&["foo", &long, "bar"]);
+        let mut pane = gen_pane!(OpenOptions::new().write(true).open("/dev/null").unwrap());
+        pane.replace_termsize_getter(Box::new(TestTerminal::new(22, 10)));
+        pane.load(texts);
+        let _ = pane.set_height(3);
+        let mut searcher = RegexSearcher::new("");
+        searcher.set_pattern("foo").unwrap();
+        pane.set_highlight_searcher(Rc::new(RefCell::new(searcher)));
+        pane.show_highlight(true);
+
+        // text area is 20 columns
+        let _ = pane.goto_match(0, 0);
+        assert_eq!(pane.position(), (0, 0));
+        assert_eq!(pane.current_match(), Some((0, 0)));
+        let _ = pane.goto_match(1, 0);
+        assert_eq!(pane.position(), (20, 1));
+        // the second match is visible without scroll
+        let _ = pane.goto_match(1, 1);
+        assert_eq!(pane.position(), (20, 1));
+        assert!(pane.is_line_visible(2));
+        assert!(!pane.is_line_visible(0));
+        // the match out of the pane is put on the middle
+        let _ = pane.goto_match(1, 0);
+        let _ = pane.goto_absolute_horizontal_offset(0);
+        let _ = pane.goto_match(1, 1);
+        assert_eq!(pane.position(), (26, 1));
+
+        // current match is drawn in the other style
+        let ranges = pane.hl_match_ranges(&long, 1);
+        assert_eq!(ranges[0].sgr, MATCH_SGR);
+        assert_eq!(ranges[1].sgr, CURRENT_MATCH_SGR);
+        let ranges = pane.hl_match_ranges("foo", 0);
+        assert_eq!(ranges[0].sgr, MATCH_SGR);
+    }
+
+    #[test]
+    fn test_hl_ranges() {
+        let mut pane = gen_pane!(OpenOptions::new().write(true).open("/dev/null").unwrap());
+        let mut searcher = RegexSearcher::new("");
+        searcher.set_pattern("foo").unwrap();
+        pane.set_highlight_searcher(Rc::new(RefCell::new(searcher)));
+        let mut set = HighlightSet::new();
+        set.add("o+ bar=red", SearchMode::default()).unwrap();
+        pane.set_highlight_set(Rc::new(RefCell::new(set)));
+
+        // the highlight set is drawn without search
+        assert_eq!(
+            pane.hl_ranges("foo bar", 0),
+            vec![Highlight::new(1, 7, "\x1b[31m")]
+        );
+        // the search matches are drawn over the highlight set
+        pane.show_highlight(true);
+        assert_eq!(
+            pane.hl_ranges("foo bar", 0),
+            vec![
+                Highlight::new(0, 3, MATCH_SGR),
+                Highlight::new(3, 7, "\x1b[31m")
+            ]
+        );
+        assert_eq!(
+            Pane::hl_words_for_trimmed("foo bar", &(0, 7), &pane.hl_ranges("foo bar", 0)),
+            "\x1b[7mfoo\x1b[m\x1b[31m bar\x1b[m"
+        );
+    }
+
+    #[test]
+    fn test_syntax() {
+        let mut pane = gen_pane!(OpenOptions::new().write(true).open("/dev/null").unwrap());
+        pane.replace_termsize_getter(Box::new(TestTerminal::new(10, 10)));
+        pane.load(gen_texts(&[r#"{"k": "value"}"#]));
+        pane.set_syntax(Some(SyntaxHighlighter::new(Language::Json)));
+        let mut searcher = RegexSearcher::new("");
+        searcher.set_pattern("al").unwrap();
+        pane.set_highlight_searcher(Rc::new(RefCell::new(searcher)));
+        pane.show_highlight(true);
+
+        // the search match splits the string token
+        let raw = r#"{"k": "value"}"#;
+        let names = "\x1b[34m";
+        let strings = "\x1b[32m";
+        assert_eq!(
+            pane.hl_ranges(raw, 0),
+            vec![
+                Highlight::new(1, 4, names),
+                Highlight::new(6, 8, strings),
+                Highlight::new(8, 10, MATCH_SGR),
+                Highlight::new(10, 13, strings),
+            ]
+        );
+
+        // the tokens are cut at the edges of the trimmed text
+        let _ = pane.scroll_right(&ScrollStep::Char(3));
+        assert_eq!(
+            pane.decorate_trim(raw, 0),
+            format!(
+                "{}{}\"\x1b[m: {}\"v\x1b[m{}al\x1b[m{}u\x1b[m\x1b[10G{}",
+                ExtendMark('+'),
+                names,
+                strings,
+                MATCH_SGR,
+                strings,
+                ExtendMark('+')
+            )
         );
     }
 }
@@ -0,0 +1,148 @@
+//! printable module
+
+use std::str;
+
+/// First code point of the private use chars that stand for the bytes of invalid UTF-8.
+/// The byte 0xXX is decoded to U+10FFXX, so that it can be shown as `<XX>`.
+const INVALID_BYTE_BASE: u32 = 0x10_ff00;
+
+/// Style of the markers of control chars and invalid bytes (inverse)
+pub const MARKER_SGR: &str = "\x1b[7m";
+pub const MARKER_END_SGR: &str = "\x1b[27m";
+
+/// Number of chars at the head of the text that are checked by `is_binary`.
+const BINARY_CHECK_CHARS: usize = 8192;
+
+/// Decode the bytes as UTF-8.
+/// Each byte of invalid sequences is kept as a private use char, which is shown as `<XX>`.
+pub fn decode_lossy(raw: &[u8]) -> String {
+    let mut text = String::with_capacity(raw.len());
+    let mut rest = raw;
+    loop {
+        match str::from_utf8(rest) {
+            Ok(valid) => {
+                text.push_str(valid);
+                return text;
+            }
+            Err(e) => {
+                let (valid, invalid) = rest.split_at(e.valid_up_to());
+                // the valid part is checked by from_utf8
+                text.push_str(str::from_utf8(valid).unwrap());
+                let n = e.error_len().unwrap_or(invalid.len());
+                for &b in &invalid[..n] {
+                    text.push(char::from_u32(INVALID_BYTE_BASE + b as u32).unwrap());
+                }
+                rest = &invalid[n..];
+            }
+        }
+    }
+}
+
+/// Encode the text decoded by `decode_lossy` back to the original bytes.
+pub fn encode_lossy(text: &str) -> Vec<u8> {
+    let mut raw = Vec::with_capacity(text.len());
+    for c in text.chars() {
+        match c as u32 {
+            n @ INVALID_BYTE_BASE..=0x10_ffff => raw.push((n - INVALID_BYTE_BASE) as u8),
+            _ => raw.extend_from_slice(c.encode_utf8(&mut [0u8; 4]).as_bytes()),
+        }
+    }
+    raw
+}
+
+/// Return the number of bytes at the end of the data that may be continued by the next data
+/// to make a UTF-8 char.
+pub fn incomplete_tail_len(raw: &[u8]) -> usize {
+    for i in 1..=raw.len().min(3) {
+        let b = raw[raw.len() - i];
+        if b & 0xc0 == 0x80 {
+            // continuation byte
+            continue;
+        }
+        let len = match b {
+            0xc0..=0xdf => 2,
+            0xe0..=0xef => 3,
+            0xf0..=0xf7 => 4,
+            _ => 0,
+        };
+        return if len > i { i } else { 0 };
+    }
+    0
+}
+
+/// Return the visible marker of the char if it isn't printable:
+/// `^A` for ASCII control chars, `<XX>` for invalid bytes and `<U+0085>` for the other controls.
+/// Tabs are printable because they are expanded to spaces.
+pub fn marker_of(c: char) -> Option<String> {
+    match c as u32 {
+        0x09 => None,
+        n @ (0x00..=0x1f | 0x7f) => Some(format!("^{}", (n as u8 ^ 0x40) as char)),
+        n @ INVALID_BYTE_BASE..=0x10_ffff => Some(format!("<{:02X}>", n - INVALID_BYTE_BASE)),
+        n if c.is_control() => Some(format!("<U+{:04X}>", n)),
+        _ => None,
+    }
+}
+
+/// Return whether the text looks like the data of a binary file:
+/// it has NUL, or many chars at the head are not printable.
+/// Escape sequences and CR are not counted, since they are common in text files.
+pub fn is_binary(text: &str) -> bool {
+    let mut checked = 0;
+    let mut unprintable = 0;
+    for c in text.chars().take(BINARY_CHECK_CHARS) {
+        if c == '\0' {
+            return true;
+        }
+        checked += 1;
+        if c != '\x1b' && c != '\r' && marker_of(c).is_some() {
+            unprintable += 1;
+        }
+    }
+    // more than 30 percent
+    unprintable * 10 > checked * 3
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shown(text: &str) -> String {
+        text.chars()
+            .map(|c| marker_of(c).unwrap_or_else(|| c.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_decode_lossy() {
+        assert_eq!(decode_lossy("aあ\t".as_bytes()), "aあ\t");
+        assert_eq!(shown(&decode_lossy(b"a\xffb\xe3\x81")), "a<FF>b<E3><81>");
+        assert_eq!(shown(&decode_lossy(b"\xe3\x81\xe3\x81\x82")), "<E3><81>あ");
+        assert_eq!(shown("\x01\x1b[m\x7f\u{85}x"), "^A^[[m^?<U+0085>x");
+    }
+
+    #[test]
+    fn test_encode_lossy() {
+        let raw = b"a\xffb\xe3\x81\x82\xe3\x81\x00";
+        assert_eq!(encode_lossy(&decode_lossy(raw)), raw);
+        assert_eq!(encode_lossy("あ"), "あ".as_bytes());
+    }
+
+    #[test]
+    fn test_incomplete_tail_len() {
+        assert_eq!(incomplete_tail_len(b"abc"), 0);
+        assert_eq!(incomplete_tail_len(b"a\xe3\x81"), 2);
+        assert_eq!(incomplete_tail_len(b"a\xe3\x81\x82"), 0);
+        assert_eq!(incomplete_tail_len(b"\xf0"), 1);
+        assert_eq!(incomplete_tail_len(b"\x81\x82"), 0);
+        assert_eq!(incomplete_tail_len(b""), 0);
+    }
+
+    #[test]
+    fn test_is_binary() {
+        assert!(!is_binary("text\twith\x1b[31mcolor\x1b[m\r"));
+        assert!(is_binary("ELF\0"));
+        assert!(is_binary(&decode_lossy(b"\x7fELF\x02\x01\x01\xff\xfe")));
+        assert!(!is_binary(&decode_lossy(b"ok\x01 \xff\xfe bad")));
+        assert!(!is_binary(""));
+    }
+}
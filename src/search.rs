@@ -1,10 +1,23 @@
-use regex::{self, Regex};
+use crate::hexdump;
+use crate::linesource::LineSource;
+use regex::{self, Regex, RegexBuilder};
+use std::cell::RefCell;
+use std::cmp;
+use std::collections::BTreeSet;
 use std::io;
+use std::ops;
+
+/// Number of bytes of the source that are read at once to search for the hex pattern.
+const HEX_CHUNK_BYTES: u64 = 4096;
+/// Max number of offsets kept to start the search of the hex pattern from.
+const HEX_SYNC_CAPACITY: usize = 1 << 16;
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Match {
     start: usize,
     end: usize,
+    // the rest of the match that starts in the previous lines
+    continued: bool,
 }
 
 impl Match {
@@ -16,8 +29,18 @@ impl Match {
     pub fn end(&self) -> usize {
         self.end
     }
+    /// Return whether the match is the rest of the match that starts in the previous lines.
+    /// It is not counted as a match of the line.
+    #[inline]
+    pub fn is_continued(&self) -> bool {
+        self.continued
+    }
     fn new(start: usize, end: usize) -> Match {
-        Match { start, end }
+        Match {
+            start,
+            end,
+            continued: false,
+        }
     }
 }
 
@@ -45,6 +68,19 @@ pub trait Search {
     fn find(&self, text: &str) -> Option<Match>;
     fn find_iter(&self, text: &str) -> MatchIter;
     fn set_pattern(&mut self, pat: &str) -> io::Result<()>;
+
+    /// Return the matches in the line n of the source, whose text is given.
+    /// The searcher of bytes reads the bytes around the line from the source,
+    /// so that a match can span lines.
+    fn find_iter_in(&self, text: &str, _source: &dyn LineSource, _n: usize) -> MatchIter {
+        self.find_iter(text)
+    }
+
+    /// Return the first match that starts in the line n of the source.
+    fn find_in(&self, text: &str, source: &dyn LineSource, n: usize) -> Option<Match> {
+        self.find_iter_in(text, source, n)
+            .find(|m| !m.is_continued())
+    }
 }
 
 #[derive(Default)]
@@ -117,14 +153,24 @@ impl Search for PlaneSearcher {
 #[derive(Clone)]
 pub struct RegexSearcher {
     pat: Regex,
+    case_insensitive: bool,
 }
 
 impl RegexSearcher {
     pub fn new(pat: &str) -> Self {
         Self {
             pat: Regex::new(pat).unwrap(),
+            case_insensitive: false,
         }
     }
+
+    /// Create the searcher that ignores case.
+    pub fn case_insensitive(pat: &str) -> io::Result<Self> {
+        let mut searcher = Self::new("");
+        searcher.case_insensitive = true;
+        searcher.set_pattern(pat)?;
+        Ok(searcher)
+    }
 }
 
 impl Default for RegexSearcher {
@@ -157,31 +203,473 @@ impl Search for RegexSearcher {
     }
 
     fn set_pattern(&mut self, pat: &str) -> io::Result<()> {
-        let a = Regex::new(pat);
-        if let Err(e) = a {
-            // convert error from regex::Error to io::Error
-            // TODO: unify pane error list
-            return match e {
-                regex::Error::Syntax(_s) => {
-                    Err(io::Error::new(io::ErrorKind::InvalidInput, "Syntax error"))
+        self.pat = RegexBuilder::new(pat)
+            .case_insensitive(self.case_insensitive)
+            .build()
+            .map_err(|e| regex_error(pat, self.case_insensitive, e))?;
+        Ok(())
+    }
+}
+
+/// Searcher of byte sequences in the lines of the hex view.
+/// The pattern is hex digits like `dead beef`, where spaces are allowed between bytes.
+/// The bytes of the source are searched, and the matches are mapped to the hex digits
+/// before the ASCII column of the lines, so that a match can span lines.
+/// The matches don't overlap, and they are found from the head of the source like `find_iter`.
+#[derive(Clone, Default)]
+pub struct HexSearcher {
+    pat: String,
+    bytes: Vec<u8>,
+    // offsets where the search finds the same matches as from the head of the source,
+    // with the address and the size of the source that they are found in
+    syncs: RefCell<((usize, u64), BTreeSet<u64>)>,
+}
+
+impl HexSearcher {
+    pub fn new() -> Self {
+        Self {
+            pat: String::new(),
+            bytes: Vec::new(),
+            syncs: Default::default(),
+        }
+    }
+
+    /// Parse the hex digits of the pattern to the bytes.
+    fn parse(pat: &str) -> io::Result<Vec<u8>> {
+        // the columns are 1-based like the errors of regex
+        let invalid = |message| io::Error::new(io::ErrorKind::InvalidInput, message);
+        let incomplete = |column| invalid(format!("incomplete byte at column {}", column));
+        let mut digits = Vec::with_capacity(pat.len());
+        // column of the first digit of the byte being parsed
+        let mut pending = None;
+        for (column, c) in (1..).zip(pat.chars()) {
+            match (c.to_digit(16), pending) {
+                (Some(d), None) => {
+                    digits.push(d as u8);
+                    pending = Some(column);
+                }
+                (Some(d), Some(_)) => {
+                    digits.push(d as u8);
+                    pending = None;
+                }
+                (None, None) if c == ' ' => {}
+                (None, Some(p)) if c == ' ' => return Err(incomplete(p)),
+                (None, _) => {
+                    return Err(invalid(format!("invalid hex digit at column {}", column)))
+                }
+            }
+        }
+        if let Some(p) = pending {
+            return Err(incomplete(p));
+        }
+        Ok(digits.chunks(2).map(|d| (d[0] << 4) | d[1]).collect())
+    }
+
+    /// Return the ranges of the non-overlapping leftmost occurrences of the bytes in the data.
+    /// The search goes on from the end of each occurrence.
+    fn byte_ranges(&self, data: &[u8]) -> Vec<ops::Range<usize>> {
+        let n = self.bytes.len();
+        let mut ranges = Vec::new();
+        if n == 0 {
+            return ranges;
+        }
+        let mut start = 0;
+        while start + n <= data.len() {
+            if data[start..start + n] == self.bytes[..] {
+                ranges.push(start..start + n);
+                start += n;
+            } else {
+                start += 1;
+            }
+        }
+        ranges
+    }
+
+    /// Return the offset at or before the head where the search finds the same matches
+    /// as from the head of the source, that is the head of the occurrences overlapping
+    /// each other up to the head, or the nearest one of the known offsets.
+    fn sync_offset(&self, source: &dyn LineSource, head: u64, syncs: &BTreeSet<u64>) -> u64 {
+        let n = self.bytes.len() as u64;
+        let mut chunk = cmp::max(HEX_CHUNK_BYTES, n);
+        let mut origin = head;
+        loop {
+            let from = origin.saturating_sub(chunk);
+            if let Some(&sync) = syncs.range(from..=origin).next_back() {
+                return sync;
+            }
+            let data = source.read_bytes(from..origin + n - 1).unwrap_or_default();
+            // walk back over the occurrences running across the origin
+            for i in (0..(data.len() + 1).saturating_sub(n as usize)).rev() {
+                let start = from + i as u64;
+                if start + n <= origin {
+                    break;
+                }
+                if start < origin && data[i..i + n as usize] == self.bytes[..] {
+                    origin = start;
+                }
+            }
+            if from == 0 || origin >= from + n - 1 {
+                return origin;
+            }
+            chunk = cmp::min(chunk * 2, HEX_CHUNK_BYTES << 8);
+        }
+    }
+
+    /// Return the ranges of the matches running into the range of the source.
+    /// The offsets where the search can start again are added to the known ones.
+    fn source_ranges(
+        &self,
+        source: &dyn LineSource,
+        range: ops::Range<u64>,
+        syncs: &mut BTreeSet<u64>,
+    ) -> Vec<ops::Range<u64>> {
+        let n = self.bytes.len() as u64;
+        let chunk = cmp::max(HEX_CHUNK_BYTES, n);
+        let mut pos = self.sync_offset(source, range.start, syncs);
+        let mut ranges = Vec::new();
+        loop {
+            let end = cmp::min(pos + chunk, range.end);
+            let data = source.read_bytes(pos..end + n - 1).unwrap_or_default();
+            // the match running across the end is searched again from its head,
+            // except on the last chunk
+            let mut next = end;
+            for r in self.byte_ranges(&data) {
+                let r = pos + r.start as u64..pos + r.end as u64;
+                if r.start >= end {
+                    break;
+                }
+                if r.end > end {
+                    next = r.start;
+                    if end < range.end {
+                        break;
+                    }
+                }
+                if r.end > range.start {
+                    ranges.push(r);
+                }
+            }
+            if syncs.len() >= HEX_SYNC_CAPACITY {
+                syncs.clear();
+            }
+            syncs.insert(next);
+            if end == range.end {
+                return ranges;
+            }
+            pos = next;
+        }
+    }
+
+    /// Return the bytes of the hex digits in the line of the hex view with their positions.
+    /// The hex digits end at the double spaces before the ASCII column.
+    fn dumped_bytes(text: &str) -> Vec<(u8, usize)> {
+        let hex = text.split("  ").next().unwrap_or_default().as_bytes();
+        let digit = |i: usize| (hex[i] as char).to_digit(16);
+        let mut bytes = Vec::new();
+        let mut i = 0;
+        while i + 1 < hex.len() {
+            match (digit(i), digit(i + 1)) {
+                (Some(h), Some(l)) => {
+                    bytes.push((((h << 4) | l) as u8, i));
+                    i += 2;
+                }
+                _ => i += 1,
+            }
+        }
+        bytes
+    }
+}
+
+impl Search for HexSearcher {
+    fn as_str(&self) -> &str {
+        self.pat.as_str()
+    }
+
+    fn find(&self, text: &str) -> Option<Match> {
+        self.find_iter(text).next()
+    }
+
+    /// Search the bytes of the hex digits in the text of a line.
+    fn find_iter(&self, text: &str) -> MatchIter {
+        let dumped = Self::dumped_bytes(text);
+        let bytes: Vec<u8> = dumped.iter().map(|&(b, _)| b).collect();
+        let matches = self
+            .byte_ranges(&bytes)
+            .into_iter()
+            // from the first digit of the first byte to the last digit of the last byte
+            .map(|r| Match::new(dumped[r.start].1, dumped[r.end - 1].1 + 2))
+            .collect();
+        MatchIter { matches, index: 0 }
+    }
+
+    /// Search the bytes of the source around the line.
+    /// The text is searched instead if the source doesn't keep the bytes.
+    fn find_iter_in(&self, text: &str, source: &dyn LineSource, n: usize) -> MatchIter {
+        let (len, head) = match (source.byte_len(), source.offset_of_line(n)) {
+            (Some(len), Some(head)) => (len, head),
+            _ => return self.find_iter(text),
+        };
+        let mut matches = Vec::new();
+        let pat_len = self.bytes.len() as u64;
+        if pat_len > 0 && head < len {
+            let tail = source
+                .offset_of_line(n + 1)
+                .map_or(len, |t| cmp::min(t, len));
+            let mut syncs = self.syncs.borrow_mut();
+            // the known offsets are dropped if the source is another one or it was changed
+            let key = (source as *const dyn LineSource as *const () as usize, len);
+            if syncs.0 != key {
+                *syncs = (key, BTreeSet::new());
+            }
+            for r in self.source_ranges(source, head..tail, &mut syncs.1) {
+                let first = (cmp::max(r.start, head) - head) as usize;
+                let last = (cmp::min(r.end, tail) - head) as usize - 1;
+                matches.push(Match {
+                    start: hexdump::column_of(first),
+                    end: hexdump::column_of(last) + 2,
+                    continued: r.start < head,
+                });
+            }
+        }
+        MatchIter { matches, index: 0 }
+    }
+
+    fn set_pattern(&mut self, pat: &str) -> io::Result<()> {
+        self.bytes = Self::parse(pat)?;
+        self.pat = pat.to_owned();
+        self.syncs.borrow_mut().1.clear();
+        Ok(())
+    }
+}
+
+/// Convert the error from regex::Error to io::Error.
+/// The syntax error tells what is wrong and the column of the pattern where it is.
+fn regex_error(pat: &str, case_insensitive: bool, e: regex::Error) -> io::Error {
+    match e {
+        regex::Error::Syntax(s) => {
+            let parsed = regex_syntax::ParserBuilder::new()
+                .case_insensitive(case_insensitive)
+                .build()
+                .parse(pat);
+            let message = match parsed {
+                Err(regex_syntax::Error::Parse(e)) => {
+                    format!("{} at column {}", e.kind(), e.span().start.column)
                 }
-                regex::Error::CompiledTooBig(_n) => Err(io::Error::new(
-                    io::ErrorKind::InvalidInput,
-                    "Compiled too big",
-                )),
-                _ => Err(io::Error::new(io::ErrorKind::Other, "Unknown regex error")),
+                Err(regex_syntax::Error::Translate(e)) => {
+                    format!("{} at column {}", e.kind(), e.span().start.column)
+                }
+                // the last line of the message describes the error
+                _ => s
+                    .lines()
+                    .last()
+                    .map(|l| l.trim_start_matches("error: ").to_owned())
+                    .unwrap_or_else(|| "Syntax error".to_owned()),
             };
+            io::Error::new(io::ErrorKind::InvalidInput, message)
+        }
+        regex::Error::CompiledTooBig(_n) => {
+            io::Error::new(io::ErrorKind::InvalidInput, "Compiled too big")
+        }
+        _ => io::Error::other("Unknown regex error"),
+    }
+}
+
+/// How the letter case is matched
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CaseMode {
+    #[default]
+    Sensitive,
+    Insensitive,
+    /// Insensitive unless the pattern contains uppercase letters
+    Smart,
+}
+
+/// Case mode and pattern syntax of the search
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SearchMode {
+    pub case: CaseMode,
+    pub fixed_string: bool,
+}
+
+/// Searcher whose case mode and pattern syntax can be switched.
+/// The pattern is kept as typed, and the searcher is rebuilt when the mode changes.
+/// The hex mode searches the hex view for the bytes, and the other modes don't apply to it.
+pub struct ModalSearcher {
+    pat: String,
+    case_mode: CaseMode,
+    fixed_string: bool,
+    hex: bool,
+    inner: Box<dyn Search>,
+}
+
+impl Default for ModalSearcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ModalSearcher {
+    pub fn new() -> Self {
+        Self {
+            pat: String::new(),
+            case_mode: CaseMode::Sensitive,
+            fixed_string: false,
+            hex: false,
+            inner: Box::new(RegexSearcher::new("")),
         }
-        self.pat = a.unwrap();
+    }
+
+    /// Create the searcher of the mode.
+    pub fn with_mode(mode: SearchMode) -> Self {
+        Self {
+            pat: String::new(),
+            case_mode: mode.case,
+            fixed_string: mode.fixed_string,
+            hex: false,
+            inner: Box::new(RegexSearcher::new("")),
+        }
+    }
+
+    pub fn mode(&self) -> SearchMode {
+        SearchMode {
+            case: self.case_mode,
+            fixed_string: self.fixed_string,
+        }
+    }
+
+    pub fn case_mode(&self) -> CaseMode {
+        self.case_mode
+    }
+
+    pub fn is_fixed_string(&self) -> bool {
+        self.fixed_string
+    }
+
+    /// Set the case mode.
+    /// The mode is not changed if the current pattern can't be rebuilt with it.
+    pub fn set_case_mode(&mut self, mode: CaseMode) -> io::Result<()> {
+        self.inner = Self::build(&self.pat, mode, self.fixed_string, self.hex)?;
+        self.case_mode = mode;
+        Ok(())
+    }
+
+    /// Set whether the pattern is a fixed string instead of a regex.
+    /// The mode is not changed if the current pattern can't be rebuilt with it.
+    pub fn set_fixed_string(&mut self, b: bool) -> io::Result<()> {
+        self.inner = Self::build(&self.pat, self.case_mode, b, self.hex)?;
+        self.fixed_string = b;
+        Ok(())
+    }
+
+    pub fn is_hex(&self) -> bool {
+        self.hex
+    }
+
+    /// Set whether the pattern is the hex digits of bytes to search the hex view for.
+    /// The mode is not changed if the current pattern can't be rebuilt with it.
+    pub fn set_hex(&mut self, b: bool) -> io::Result<()> {
+        self.inner = Self::build(&self.pat, self.case_mode, self.fixed_string, b)?;
+        self.hex = b;
+        Ok(())
+    }
+
+    /// Return the label of the active mode for the search prompt.
+    /// The label is empty in the default mode.
+    pub fn mode_label(&self) -> String {
+        if self.hex {
+            return "Hex ".to_owned();
+        }
+        let mut label = String::new();
+        match self.case_mode {
+            CaseMode::Sensitive => {}
+            CaseMode::Insensitive => label.push_str("Ignore-case "),
+            CaseMode::Smart => label.push_str("Smart-case "),
+        }
+        if self.fixed_string {
+            label.push_str("Fixed-string ");
+        }
+        label
+    }
+
+    fn build(
+        pat: &str,
+        mode: CaseMode,
+        fixed_string: bool,
+        hex: bool,
+    ) -> io::Result<Box<dyn Search>> {
+        if hex {
+            let mut searcher = HexSearcher::new();
+            searcher.set_pattern(pat)?;
+            return Ok(Box::new(searcher));
+        }
+        let ignores_case = match mode {
+            CaseMode::Sensitive => false,
+            CaseMode::Insensitive => true,
+            CaseMode::Smart => !has_uppercase(pat, fixed_string),
+        };
+        Ok(match (fixed_string, ignores_case) {
+            (true, false) => {
+                let mut searcher = PlaneSearcher::new();
+                searcher.set_pattern(pat)?;
+                Box::new(searcher)
+            }
+            (true, true) => Box::new(RegexSearcher::case_insensitive(&regex::escape(pat))?),
+            (false, false) => {
+                let mut searcher = RegexSearcher::new("");
+                searcher.set_pattern(pat)?;
+                Box::new(searcher)
+            }
+            (false, true) => Box::new(RegexSearcher::case_insensitive(pat)?),
+        })
+    }
+}
+
+impl Search for ModalSearcher {
+    fn as_str(&self) -> &str {
+        self.pat.as_str()
+    }
+
+    fn find(&self, text: &str) -> Option<Match> {
+        self.inner.find(text)
+    }
+
+    fn find_iter(&self, text: &str) -> MatchIter {
+        self.inner.find_iter(text)
+    }
+
+    fn find_iter_in(&self, text: &str, source: &dyn LineSource, n: usize) -> MatchIter {
+        self.inner.find_iter_in(text, source, n)
+    }
+
+    fn set_pattern(&mut self, pat: &str) -> io::Result<()> {
+        self.inner = Self::build(pat, self.case_mode, self.fixed_string, self.hex)?;
+        self.pat = pat.to_owned();
         Ok(())
     }
 }
 
+/// Return whether the pattern contains uppercase letters.
+/// Escaped letters of a regex like `\W` are not counted.
+fn has_uppercase(pat: &str, fixed_string: bool) -> bool {
+    let mut chars = pat.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' && !fixed_string {
+            chars.next();
+        } else if c.is_uppercase() {
+            return true;
+        }
+    }
+    false
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::hexdump::HexSource;
+    use std::cell::RefCell;
+    use std::rc::Rc;
 
     #[test]
+    #[allow(clippy::needless_borrow)]
     fn test_plane() {
         let pat = "abc";
         let text = "xabcabcwowabc";
@@ -213,6 +701,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::needless_borrow)]
     fn test_regex() {
         let pat = r"a\wc";
         let text = "xabcabcwowabc";
@@ -246,8 +735,210 @@ mod tests {
         // syntax error
         let pat = r"++";
         assert_eq!(
-            searcher.set_pattern(&pat).unwrap_err().to_string(),
-            "Syntax error"
+            searcher.set_pattern(pat).unwrap_err().to_string(),
+            "repetition operator missing expression at column 1"
+        );
+        let pat = r"ab(c";
+        assert_eq!(
+            searcher.set_pattern(pat).unwrap_err().to_string(),
+            "unclosed group at column 3"
+        );
+        let pat = r"a\p{Foo}";
+        assert_eq!(
+            searcher.set_pattern(pat).unwrap_err().to_string(),
+            "Unicode property not found at column 2"
+        );
+        // the previous pattern is kept
+        assert_eq!(searcher.as_str(), r"a\wc");
+    }
+
+    #[test]
+    fn test_modal() {
+        let text = "Error: error ERROR a.c abc";
+
+        let mut searcher = ModalSearcher::new();
+        searcher.set_pattern("error").unwrap();
+        assert_eq!(searcher.as_str(), "error");
+        assert_eq!(searcher.find_iter(text).count(), 1);
+
+        searcher.set_case_mode(CaseMode::Insensitive).unwrap();
+        assert_eq!(searcher.find_iter(text).count(), 3);
+        assert_eq!(searcher.mode_label(), "Ignore-case ");
+
+        // smart-case ignores case only for lowercase pattern
+        searcher.set_case_mode(CaseMode::Smart).unwrap();
+        assert_eq!(searcher.find_iter(text).count(), 3);
+        searcher.set_pattern("Error").unwrap();
+        assert_eq!(searcher.find(text).unwrap(), Match::new(0, 5));
+        assert_eq!(searcher.find_iter(text).count(), 1);
+        searcher.set_pattern(r"\Werror").unwrap();
+        assert_eq!(searcher.find_iter(text).count(), 2);
+
+        // fixed string
+        searcher.set_case_mode(CaseMode::Sensitive).unwrap();
+        searcher.set_fixed_string(true).unwrap();
+        searcher.set_pattern("a.c").unwrap();
+        assert_eq!(searcher.find_iter(text).count(), 1);
+        searcher.set_pattern("A.C").unwrap();
+        assert!(searcher.find(text).is_none());
+        searcher.set_case_mode(CaseMode::Insensitive).unwrap();
+        assert_eq!(searcher.find(text).unwrap(), Match::new(19, 22));
+        assert_eq!(searcher.mode_label(), "Ignore-case Fixed-string ");
+        searcher.set_fixed_string(false).unwrap();
+        assert_eq!(searcher.find_iter(text).count(), 2);
+
+        // the mode is kept when the pattern is invalid as regex
+        searcher.set_fixed_string(true).unwrap();
+        searcher.set_pattern("++").unwrap();
+        assert_eq!(searcher.find(text), None);
+        assert!(searcher.set_fixed_string(false).is_err());
+        assert!(searcher.is_fixed_string());
+    }
+
+    #[test]
+    fn test_hex() {
+        let text = "7f45 4c46 0201 0100 7f45 4c46 0000 0000  .ELF.....ELF....";
+
+        let mut searcher = HexSearcher::new();
+        searcher.set_pattern("7f 454c").unwrap();
+        assert_eq!(searcher.find(text).unwrap(), Match::new(0, 7));
+        let mut matches = searcher.find_iter(text);
+        assert_eq!(matches.next().unwrap(), Match::new(0, 7));
+        assert_eq!(matches.next().unwrap(), Match::new(20, 27));
+        assert!(matches.next().is_none());
+
+        // bytes across the groups, and not in the ASCII column
+        searcher.set_pattern("4602").unwrap();
+        assert_eq!(searcher.find(text).unwrap(), Match::new(7, 12));
+        searcher.set_pattern("2e45").unwrap();
+        assert!(searcher.find(text).is_none());
+        searcher.set_pattern("").unwrap();
+        assert!(searcher.find(text).is_none());
+
+        assert_eq!(
+            searcher.set_pattern("7f4g").unwrap_err().to_string(),
+            "invalid hex digit at column 4"
         );
+        assert_eq!(
+            searcher.set_pattern("7 f").unwrap_err().to_string(),
+            "incomplete byte at column 1"
+        );
+        assert_eq!(
+            searcher.set_pattern("7f4").unwrap_err().to_string(),
+            "incomplete byte at column 3"
+        );
+
+        // hex mode of the modal searcher
+        let mut searcher = ModalSearcher::new();
+        searcher.set_pattern("ELF").unwrap();
+        assert!(searcher.set_hex(true).is_err());
+        searcher.set_pattern("").unwrap();
+        searcher.set_hex(true).unwrap();
+        // the matches don't overlap
+        searcher.set_pattern("000000").unwrap();
+        assert_eq!(searcher.find_iter(text).count(), 1);
+        searcher.set_pattern("0000").unwrap();
+        assert_eq!(searcher.find_iter(text).count(), 2);
+        assert_eq!(searcher.mode_label(), "Hex ");
+        searcher.set_hex(false).unwrap();
+        assert_eq!(searcher.find_iter(text).count(), 2);
+    }
+
+    #[test]
+    fn test_hex_source() {
+        // "0123456789abcdef" on the line 0, and "ghij\naaaa\n" on the line 1
+        let base = Rc::new(RefCell::new(vec![
+            "0123456789abcdefghij".to_owned(),
+            "aaaa".to_owned(),
+        ]));
+        let source = HexSource::new(base);
+        let line = |n| source.line(n).unwrap_or_default();
+        let mut searcher = HexSearcher::new();
+
+        // "efgh" spans the lines
+        searcher.set_pattern("6566 6768").unwrap();
+        let matches: Vec<Match> = searcher.find_iter_in(&line(0), &source, 0).collect();
+        assert_eq!(matches, vec![Match::new(35, 39)]);
+        let matches: Vec<Match> = searcher.find_iter_in(&line(1), &source, 1).collect();
+        assert_eq!(
+            matches,
+            vec![Match {
+                start: 0,
+                end: 4,
+                continued: true
+            }]
+        );
+        assert!(searcher.find_in(&line(1), &source, 1).is_none());
+
+        // longer than a line
+        searcher
+            .set_pattern("30313233343536373839616263646566 67")
+            .unwrap();
+        assert_eq!(
+            searcher.find_in(&line(0), &source, 0),
+            Some(Match::new(0, 39))
+        );
+        assert_eq!(searcher.find_iter_in(&line(1), &source, 1).count(), 1);
+        assert!(searcher.find_in(&line(1), &source, 1).is_none());
+
+        // "aaaa" has two matches of "aa"
+        searcher.set_pattern("6161").unwrap();
+        let matches: Vec<Match> = searcher.find_iter_in(&line(1), &source, 1).collect();
+        assert_eq!(matches, vec![Match::new(12, 17), Match::new(17, 22)]);
+        assert!(searcher.find_in(&line(2), &source, 2).is_none());
+    }
+
+    #[test]
+    fn test_hex_source_overlapping() {
+        // 40 bytes of "a" and LF on the lines 0 to 2
+        let base = Rc::new(RefCell::new(vec!["a".repeat(40)]));
+        let source = HexSource::new(base);
+        let line = |n| source.line(n).unwrap_or_default();
+        let matches_of = |searcher: &HexSearcher, n| {
+            searcher
+                .find_iter_in(&line(n), &source, n)
+                .collect::<Vec<Match>>()
+        };
+
+        // "aaa" matches at the bytes 0, 3, ..., 36 as from the head of the source
+        let mut searcher = HexSearcher::new();
+        searcher.set_pattern("616161").unwrap();
+        let count: usize = (0..3)
+            .map(|n| {
+                searcher
+                    .find_iter_in(&line(n), &source, n)
+                    .filter(|m| !m.is_continued())
+                    .count()
+            })
+            .sum();
+        assert_eq!(count, 13);
+        let sequential = matches_of(&searcher, 2);
+        assert_eq!(
+            sequential,
+            vec![
+                Match {
+                    start: 0,
+                    end: 2,
+                    continued: true
+                },
+                Match::new(2, 9),
+                Match::new(10, 17),
+            ]
+        );
+
+        // a line is searched the same without the lines before it
+        let mut searcher = HexSearcher::new();
+        searcher.set_pattern("616161").unwrap();
+        assert_eq!(matches_of(&searcher, 2), sequential);
+        assert_eq!(searcher.find_iter_in(&line(1), &source, 1).count(), 6);
+    }
+
+    #[test]
+    fn test_has_uppercase() {
+        assert!(!has_uppercase("abc", false));
+        assert!(has_uppercase("aBc", false));
+        assert!(!has_uppercase(r"\W\d", false));
+        assert!(has_uppercase(r"\W", true));
+        assert!(has_uppercase("ÄB", false));
     }
 }
@@ -0,0 +1,305 @@
+//! syntax module
+
+use crate::ansi::Highlight;
+use regex::{CaptureLocations, Regex};
+use std::io;
+use std::path::Path;
+
+// styles of the tokens
+const COMMENT: &str = "\x1b[90m";
+const KEYWORD: &str = "\x1b[35m";
+const STRING: &str = "\x1b[32m";
+const NUMBER: &str = "\x1b[36m";
+const TYPE: &str = "\x1b[33m";
+const NAME: &str = "\x1b[34m";
+const HEADER: &str = "\x1b[1m";
+const ADDED: &str = "\x1b[32m";
+const REMOVED: &str = "\x1b[31m";
+
+/// Language whose tokens are colored
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Language {
+    Rust,
+    Json,
+    Yaml,
+    Toml,
+    Diff,
+}
+
+impl Language {
+    /// Return the language of the name given by `--language`.
+    pub fn from_name(name: &str) -> io::Result<Self> {
+        Ok(match name.to_ascii_lowercase().as_str() {
+            "rust" | "rs" => Language::Rust,
+            "json" | "jsonl" => Language::Json,
+            "yaml" | "yml" => Language::Yaml,
+            "toml" => Language::Toml,
+            "diff" | "patch" => Language::Diff,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("unknown language: {}", name),
+                ))
+            }
+        })
+    }
+
+    /// Return the language of the file extension.
+    pub fn from_path(path: &str) -> Option<Self> {
+        let ext = Path::new(path).extension()?.to_str()?;
+        Self::from_name(ext).ok()
+    }
+
+    /// Return the token rules as the regex and the style.
+    /// The first capture group is styled if the regex has it, otherwise the whole match is.
+    fn rules(&self) -> Vec<(&'static str, &'static str)> {
+        const DQ_STRING: &str = r#""(?:\\.|[^"\\])*"?"#;
+        match self {
+            Language::Rust => vec![
+                (r"//.*", COMMENT),
+                (r"/\*.*?(?:\*/|$)", COMMENT),
+                (r##"\bb?r#*".*?"#*"##, STRING),
+                (r#"b?"(?:\\.|[^"\\])*"?"#, STRING),
+                (r"b?'(?:\\.|[^'\\])'", STRING),
+                (r"'[A-Za-z_]\w*", TYPE),
+                (r"#!?\[[^\]]*\]?", TYPE),
+                (
+                    r"\b(?:as|async|await|break|const|continue|crate|dyn|else|enum|extern|false|fn|for|if|impl|in|let|loop|match|mod|move|mut|pub|ref|return|self|Self|static|struct|super|trait|true|type|unsafe|use|where|while)\b",
+                    KEYWORD,
+                ),
+                (r"\b[A-Z][A-Za-z0-9_]*\b", TYPE),
+                (r"\b[a-z_][a-z0-9_]*!", NAME),
+                (
+                    r"\b(?:0x[0-9a-fA-F_]+|0o[0-7_]+|0b[01_]+|\d[\d_]*(?:\.\d[\d_]*)?(?:[eE][+-]?\d+)?)(?:[iuf](?:8|16|32|64|128|size))?\b",
+                    NUMBER,
+                ),
+            ],
+            Language::Json => vec![
+                (r#"("(?:\\.|[^"\\])*")\s*:"#, NAME),
+                (DQ_STRING, STRING),
+                (r"-?\b\d+(?:\.\d+)?(?:[eE][+-]?\d+)?\b", NUMBER),
+                (r"\b(?:true|false|null)\b", KEYWORD),
+            ],
+            Language::Yaml => vec![
+                (r"(?:^|\s)(#.*)", COMMENT),
+                (r"^(?:---|\.\.\.)(?:\s|$)", KEYWORD),
+                (
+                    r#"^\s*(?:-\s+)?("(?:\\.|[^"\\])*"|'[^']*'|[^\s#'"\-][^#:]*?)\s*:(?:\s|$)"#,
+                    NAME,
+                ),
+                (r#""(?:\\.|[^"\\])*"?|'(?:''|[^'])*'?"#, STRING),
+                (r"[&*][\w\-]+|!!?[\w/]*", TYPE),
+                (r"\b(?:true|false|yes|no|on|off|null)\b|~", KEYWORD),
+                (r"-?\b\d+(?:\.\d+)?\b", NUMBER),
+            ],
+            Language::Toml => vec![
+                (r"#.*", COMMENT),
+                (r"^\s*\[\[?[^\]]*\]\]?", TYPE),
+                (r#"^\s*("[^"]*"|'[^']*'|[\w.\-]+)\s*="#, NAME),
+                (r#""(?:\\.|[^"\\])*"?|'[^']*'?"#, STRING),
+                (r"\b(?:true|false)\b", KEYWORD),
+                (
+                    r"[+-]?\b\d[\d_]*(?:[.:\-]\d[\d_]*)*(?:[eE][+-]?\d+)?\b|\b(?:inf|nan)\b",
+                    NUMBER,
+                ),
+            ],
+            Language::Diff => vec![
+                (r"^(?:diff|index|\+\+\+|---) .*", HEADER),
+                (r"^@@.*?@@", NAME),
+                (r"^\+.*", ADDED),
+                (r"^-.*", REMOVED),
+            ],
+        }
+    }
+}
+
+struct Rule {
+    pat: Regex,
+    sgr: &'static str,
+}
+
+/// Token found by a rule: the range of the match and the range to style
+#[derive(Clone, Copy)]
+struct Token {
+    start: usize,
+    end: usize,
+    styled: (usize, usize),
+}
+
+/// Colorer of the tokens of the language.
+/// Each line is colored by itself, so tokens spanning lines are colored only on their first line.
+pub struct SyntaxHighlighter {
+    language: Language,
+    rules: Vec<Rule>,
+}
+
+impl SyntaxHighlighter {
+    pub fn new(language: Language) -> Self {
+        let rules = language
+            .rules()
+            .into_iter()
+            .map(|(pat, sgr)| Rule {
+                pat: Regex::new(pat).unwrap(),
+                sgr,
+            })
+            .collect();
+        Self { language, rules }
+    }
+
+    pub fn language(&self) -> Language {
+        self.language
+    }
+
+    /// Return the highlight ranges of the tokens in the text.
+    /// The token that starts first wins, and the former rule wins at the same position.
+    pub fn ranges(&self, text: &str) -> Vec<Highlight> {
+        let mut locs: Vec<CaptureLocations> = self
+            .rules
+            .iter()
+            .map(|r| r.pat.capture_locations())
+            .collect();
+        // the next token of each rule, None if there are no more tokens
+        let mut tokens: Vec<Option<Token>> = (0..self.rules.len())
+            .map(|i| self.find_token(i, text, 0, &mut locs[i]))
+            .collect();
+        let mut highlights = vec![];
+        let mut pos = 0;
+
+        while pos <= text.len() {
+            let mut first: Option<(usize, Token)> = None;
+            for i in 0..self.rules.len() {
+                if matches!(tokens[i], Some(t) if t.start < pos) {
+                    tokens[i] = self.find_token(i, text, pos, &mut locs[i]);
+                }
+                if let Some(t) = tokens[i] {
+                    if first.is_none_or(|(_, f)| t.start < f.start) {
+                        first = Some((i, t));
+                    }
+                }
+            }
+            let (i, t) = match first {
+                Some(first) => first,
+                None => break,
+            };
+            if t.styled.0 < t.styled.1 {
+                highlights.push(Highlight::new(t.styled.0, t.styled.1, self.rules[i].sgr));
+            }
+            pos = if t.end > t.start {
+                t.end
+            } else {
+                // step over the empty match
+                t.end + text[t.end..].chars().next().map_or(1, |c| c.len_utf8())
+            };
+        }
+        highlights
+    }
+
+    fn find_token(
+        &self,
+        i: usize,
+        text: &str,
+        pos: usize,
+        locs: &mut CaptureLocations,
+    ) -> Option<Token> {
+        if pos > text.len() {
+            return None;
+        }
+        let m = self.rules[i].pat.captures_read_at(locs, text, pos)?;
+        Some(Token {
+            start: m.start(),
+            end: m.end(),
+            styled: locs.get(1).unwrap_or((m.start(), m.end())),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn styled<'a>(text: &'a str, ranges: &[Highlight]) -> Vec<(&'a str, String)> {
+        ranges
+            .iter()
+            .map(|hl| (&text[hl.start..hl.end], hl.sgr.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_language() {
+        assert_eq!(Language::from_path("src/main.rs"), Some(Language::Rust));
+        assert_eq!(Language::from_path("a/b.YML"), Some(Language::Yaml));
+        assert_eq!(Language::from_path("Makefile"), None);
+        assert_eq!(Language::from_path("a.txt"), None);
+        assert_eq!(Language::from_name("patch").unwrap(), Language::Diff);
+        assert!(Language::from_name("cobol").is_err());
+    }
+
+    #[test]
+    fn test_rust() {
+        let hl = SyntaxHighlighter::new(Language::Rust);
+        let text = r#"let s: String = "a // b".into(); // done 42"#;
+        assert_eq!(
+            styled(text, &hl.ranges(text)),
+            vec![
+                ("let", KEYWORD.to_owned()),
+                ("String", TYPE.to_owned()),
+                (r#""a // b""#, STRING.to_owned()),
+                ("// done 42", COMMENT.to_owned()),
+            ]
+        );
+        let text = "fn f<'a>(x: &'a u8) { println!(\"{}\", 0x1f + 2_u8) }";
+        assert_eq!(
+            styled(text, &hl.ranges(text)),
+            vec![
+                ("fn", KEYWORD.to_owned()),
+                ("'a", TYPE.to_owned()),
+                ("'a", TYPE.to_owned()),
+                ("println!", NAME.to_owned()),
+                ("\"{}\"", STRING.to_owned()),
+                ("0x1f", NUMBER.to_owned()),
+                ("2_u8", NUMBER.to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_json_and_yaml() {
+        let hl = SyntaxHighlighter::new(Language::Json);
+        let text = r#"{"id": 1.5e3, "ok": true, "msg": "a: \"b\""}"#;
+        assert_eq!(
+            styled(text, &hl.ranges(text)),
+            vec![
+                (r#""id""#, NAME.to_owned()),
+                ("1.5e3", NUMBER.to_owned()),
+                (r#""ok""#, NAME.to_owned()),
+                ("true", KEYWORD.to_owned()),
+                (r#""msg""#, NAME.to_owned()),
+                (r#""a: \"b\"""#, STRING.to_owned()),
+            ]
+        );
+
+        let hl = SyntaxHighlighter::new(Language::Yaml);
+        let text = r#"  - name: "x # y" # comment"#;
+        assert_eq!(
+            styled(text, &hl.ranges(text)),
+            vec![
+                ("name", NAME.to_owned()),
+                (r#""x # y""#, STRING.to_owned()),
+                ("# comment", COMMENT.to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff() {
+        let hl = SyntaxHighlighter::new(Language::Diff);
+        assert_eq!(hl.ranges("--- a/x"), vec![Highlight::new(0, 7, HEADER)]);
+        assert_eq!(hl.ranges("-old"), vec![Highlight::new(0, 4, REMOVED)]);
+        assert_eq!(
+            hl.ranges("@@ -1 +1 @@ fn"),
+            vec![Highlight::new(0, 11, NAME)]
+        );
+        assert!(hl.ranges(" same").is_empty());
+        assert!(hl.ranges("").is_empty());
+    }
+}
@@ -1,15 +1,40 @@
 //! tab module
 
+use crate::printable;
+use std::ops;
 use unicode_width::UnicodeWidthChar;
 
 const TAB_SPACE: &str = "                                ";
 
+/// Expansion of tabs to spaces.
+/// Control chars and invalid bytes are replaced with their visible markers like `^A` and `<FF>`.
 pub trait TabExpand {
     fn expand_tab(&self, tab_width: usize) -> String;
+    /// Expand tabs of the string that starts at the column.
+    fn expand_tab_from(&self, tab_width: usize, column: usize) -> String;
+    /// Expand tabs of the string that starts at the column,
+    /// and return the byte ranges of the markers in the expanded string.
+    fn expand_tab_marked(
+        &self,
+        tab_width: usize,
+        column: usize,
+    ) -> (String, Vec<ops::Range<usize>>);
 }
 
 impl TabExpand for str {
     fn expand_tab(&self, tab_width: usize) -> String {
+        self.expand_tab_from(tab_width, 0)
+    }
+
+    fn expand_tab_from(&self, tab_width: usize, column: usize) -> String {
+        self.expand_tab_marked(tab_width, column).0
+    }
+
+    fn expand_tab_marked(
+        &self,
+        tab_width: usize,
+        column: usize,
+    ) -> (String, Vec<ops::Range<usize>>) {
         let tab_width = if tab_width > TAB_SPACE.len() {
             TAB_SPACE.len()
         } else {
@@ -17,10 +42,16 @@ impl TabExpand for str {
         };
 
         let mut expanded_str = String::new();
-        let mut expand_width = 0;
+        let mut markers = vec![];
+        let mut expand_width = column;
 
         for c in self.chars() {
-            expand_width += if c == '\t' {
+            expand_width += if let Some(marker) = printable::marker_of(c) {
+                let start = expanded_str.len();
+                expanded_str.push_str(&marker);
+                markers.push(start..expanded_str.len());
+                marker.len()
+            } else if c == '\t' {
                 if tab_width > 0 {
                     let frac = tab_width - (expand_width % tab_width);
                     expanded_str.push_str(&TAB_SPACE[0..frac]);
@@ -35,7 +66,7 @@ impl TabExpand for str {
                 })
             }
         }
-        expanded_str
+        (expanded_str, markers)
     }
 }
 
@@ -66,4 +97,19 @@ mod tests {
             assert_eq!(d.0.expand_tab(4), d.1);
         }
     }
+
+    #[test]
+    fn test_tab_from_column() {
+        assert_eq!("\t9".expand_tab_from(4, 1), "   9");
+        assert_eq!("\t9".expand_tab_from(4, 4), "    9");
+        assert_eq!("12\t9".expand_tab_from(4, 5), "12 9");
+    }
+
+    #[test]
+    fn test_tab_marked() {
+        assert_eq!(
+            "\x01\t9\u{10ffe3}".expand_tab_marked(4, 0),
+            ("^A  9<E3>".to_owned(), vec![0..2, 5..9])
+        );
+    }
 }
@@ -1,5 +1,6 @@
-use std::fs::File;
-use std::io::{self, Stdin};
+use crate::csi::cursor_ext;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Stdin, Write};
 use std::mem;
 use std::os::unix::io::AsRawFd;
 use std::os::unix::io::RawFd;
@@ -170,6 +171,48 @@ impl Block for Stdin {
     }
 }
 
+/// Ask the terminal for the cursor position and return (column, row).
+/// The terminal must be in non-canonical mode, and nothing else may read the tty meanwhile.
+pub fn dev_tty_cursor_position(timeout_ms: i32) -> io::Result<(u16, u16)> {
+    use nix::poll::{poll, PollFd, PollFlags};
+
+    let mut ftty = OpenOptions::new().read(true).write(true).open("/dev/tty")?;
+    write!(ftty, "{}", cursor_ext::RequestPosition)?;
+    ftty.flush()?;
+
+    // response: ESC [ row ; column R
+    let mut report = Vec::new();
+    let mut buf = [0u8; 1];
+    loop {
+        let mut fds = [PollFd::new(ftty.as_raw_fd(), PollFlags::POLLIN)];
+        if !matches!(poll(&mut fds, timeout_ms), Ok(n) if n > 0) {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "no cursor position report",
+            ));
+        }
+        if ftty.read(&mut buf)? == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        if buf[0] == b'R' {
+            break;
+        }
+        report.push(buf[0]);
+    }
+    let report = String::from_utf8_lossy(&report);
+    let mut nums = report
+        .trim_start_matches(|c: char| !c.is_ascii_digit())
+        .split(';')
+        .map(|n| n.parse::<u16>().ok());
+    match (nums.next().flatten(), nums.next().flatten()) {
+        (Some(row), Some(col)) => Ok((col, row)),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "invalid cursor position report",
+        )),
+    }
+}
+
 pub fn dev_tty_size() -> io::Result<(u16, u16)> {
     #[repr(C)]
     struct WinSize {
@@ -116,6 +116,7 @@ mod tests {
     use super::*;
 
     #[test]
+    #[allow(clippy::needless_borrow)]
     fn test_iterator() {
         let ascii_sentence = "1234567890";
         let unicode_sentence = "あいうえお";
@@ -144,6 +145,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::needless_borrow)]
     fn test_seek() {
         let ascii_sentence = "1234567890";
         let unicode_sentence = "あいうえお";